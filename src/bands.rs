@@ -0,0 +1,40 @@
+/// The fewest items (cells, or words of cells) worth starting a thread for:
+/// fewer take less time to step than the thread takes to start.
+pub const MIN_BAND: usize = 1024;
+
+/// Splits `rows`, made of consecutive rows of `row_length` items (the last
+/// one may be shorter), into one band of whole rows per thread and calls
+/// `fill` on each band in parallel with the index of its first row. Uses
+/// fewer threads, down to just the calling one, so that no band has fewer
+/// than `min_band` items.
+pub fn fill<T: Send>(
+    rows: &mut [T],
+    row_length: usize,
+    threads: usize,
+    min_band: usize,
+    fill: impl Fn(usize, &mut [T]) + Sync,
+) {
+    let row_count = rows.len().div_ceil(row_length.max(1));
+    let threads = threads
+        .min(rows.len() / min_band.max(1))
+        .clamp(1, row_count.max(1));
+    if threads == 1 {
+        fill(0, rows);
+        return;
+    }
+
+    let band_rows = row_count.div_ceil(threads);
+    let fill = &fill;
+    std::thread::scope(|scope| {
+        for (band, chunk) in rows.chunks_mut(band_rows * row_length).enumerate() {
+            scope.spawn(move || fill(band * band_rows, chunk));
+        }
+    });
+}
+
+/// The number of threads to use when none is given.
+pub fn default_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|threads| threads.get())
+        .unwrap_or(1)
+}
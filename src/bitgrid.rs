@@ -0,0 +1,415 @@
+use crate::bands;
+use crate::engine::{self, Engine};
+use crate::neighborhood::Neighborhood;
+use crate::rule::{CellState, Change, Rule};
+use crate::tiles::Tiles;
+use crate::topology::Topology;
+
+/// A fixed-size universe like `Grid`, with one bit per cell packed into
+/// `u64` words so that 64 cells are stepped at once with bitwise adders.
+///
+/// Only works for two-state rules on the Moore or von Neumann neighborhood
+/// that depend on nothing but the number of live neighbors.
+pub struct BitGrid {
+    width: usize,
+    height: usize,
+    topology: Topology,
+    rule: Rule,
+    // bit n set when n live neighbors make a dead cell alive or keep a live
+    // one alive
+    birth: u16,
+    survival: u16,
+    // words per row
+    stride: usize,
+    // rows with a ring of ghost cells around them, copied from wherever the
+    // topology joins the edges to: cell (i, j) is bit j + 1 of row i + 1.
+    // The rows are padded to a whole number of tiles.
+    cells: Vec<u64>,
+    // kept the same as `cells` outside the tiles stepped, as `Tiles` explains
+    next: Vec<u64>,
+    // tiles are a word wide and `TILE_ROWS` rows high
+    tiles: Tiles,
+    changes: Vec<Change>,
+    threads: usize,
+}
+
+const TILE_ROWS: usize = 16;
+
+impl BitGrid {
+    pub fn supports(rule: &Rule) -> bool {
+        matches!(
+            rule.neighborhood(),
+            Neighborhood::MOORE | Neighborhood::VONNEUMANN
+        ) && rule.totalistic().is_some()
+    }
+
+    pub fn new(rule: Rule, topology: Topology) -> Result<Self, String> {
+        let (birth, survival) = match rule.totalistic() {
+            Some(tables) if BitGrid::supports(&rule) => tables,
+            _ => {
+                return Err(format!(
+                    "bitgrid needs a two-state totalistic Moore or von Neumann rule, not {}",
+                    rule
+                ))
+            }
+        };
+        let mask = |table: Vec<bool>| {
+            table
+                .iter()
+                .enumerate()
+                .fold(0, |mask, (n, &on)| mask | (on as u16) << n)
+        };
+
+        let (width, height) = (topology.width, topology.height);
+        let stride = (width + 2).div_ceil(64);
+        let tile_rows = height.div_ceil(TILE_ROWS);
+        let words = (tile_rows * TILE_ROWS + 2) * stride;
+        Ok(BitGrid {
+            width,
+            height,
+            topology,
+            rule,
+            birth: mask(birth),
+            survival: mask(survival),
+            stride,
+            cells: vec![0; words],
+            next: vec![0; words],
+            tiles: Tiles::new(stride, tile_rows),
+            changes: Vec::new(),
+            threads: 1,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    /// Splits each generation into bands of rows stepped on this many
+    /// threads.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> CellState {
+        CellState(self.bit(i + 1, j + 1) as u8)
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, state: CellState) {
+        self.put(i + 1, j + 1, state == CellState::ALIVE);
+        self.tiles.mark((j + 1) / 64, i / TILE_ROWS);
+        if i == 0 || j == 0 || i + 1 == self.height || j + 1 == self.width {
+            self.tiles.mark_edge();
+        }
+    }
+
+    /// Advances one generation and returns the cells that changed, with
+    /// `x` the column and `y` the row.
+    pub fn tick(&mut self) -> &[Change] {
+        if self.tiles.edge_changed() {
+            self.fill_ghosts();
+        }
+        let active = self.tiles.take_active();
+
+        let stride = self.stride;
+        let mut next_cells = std::mem::take(&mut self.next);
+        let grid = &*self;
+        let rows = &mut next_cells[stride..self.cells.len() - stride];
+        bands::fill(
+            rows,
+            TILE_ROWS * stride,
+            self.threads,
+            bands::MIN_BAND,
+            |first_tile_row, band| {
+                let last_tile_row = first_tile_row + band.len() / (TILE_ROWS * stride);
+                let start = active.partition_point(|&tile| tile / stride < first_tile_row);
+                let end = active.partition_point(|&tile| tile / stride < last_tile_row);
+                for &tile in &active[start..end] {
+                    let (k, tile_row) = (tile % stride, tile / stride);
+                    for i in tile_row * TILE_ROWS..((tile_row + 1) * TILE_ROWS).min(grid.height) {
+                        band[(i - first_tile_row * TILE_ROWS) * stride + k] =
+                            grid.step_word(i + 1, k);
+                    }
+                }
+            },
+        );
+
+        self.changes.clear();
+        let mut changed_words = Vec::new();
+        for &tile in &active {
+            let (k, tile_row) = (tile % stride, tile / stride);
+            for i in tile_row * TILE_ROWS..((tile_row + 1) * TILE_ROWS).min(self.height) {
+                let word = (i + 1) * stride + k;
+                let (old, new) = (self.cells[word], next_cells[word]);
+                let mut flipped = old ^ new;
+                if flipped == 0 {
+                    continue;
+                }
+
+                changed_words.push(word);
+                self.tiles.mark(k, tile_row);
+                let last = self.width / 64;
+                if i == 0
+                    || i + 1 == self.height
+                    || (k == 0 && flipped & 0b10 != 0)
+                    || (k == last && flipped >> (self.width % 64) & 1 != 0)
+                {
+                    self.tiles.mark_edge();
+                }
+                while flipped != 0 {
+                    let bit = flipped.trailing_zeros() as usize;
+                    flipped &= flipped - 1;
+                    self.changes.push(Change {
+                        x: (k * 64 + bit - 1) as i64,
+                        y: i as i64,
+                        state: CellState((new >> bit) as u8 & 1),
+                    });
+                }
+            }
+        }
+
+        self.next = std::mem::replace(&mut self.cells, next_cells);
+        for word in changed_words {
+            self.next[word] = self.cells[word];
+        }
+        &self.changes
+    }
+
+    // the next generation of word `k` of `row`, counting the ghost row as 0,
+    // leaving the ghost bits as they are
+    fn step_word(&self, row: usize, k: usize) -> u64 {
+        let stride = self.stride;
+        let above = &self.cells[(row - 1) * stride..row * stride];
+        let current = &self.cells[row * stride..(row + 1) * stride];
+        let below = &self.cells[(row + 1) * stride..(row + 2) * stride];
+
+        let counts = match self.rule.neighborhood() {
+            Neighborhood::VONNEUMANN => {
+                count4([above[k], west(current, k), east(current, k), below[k]])
+            }
+            _ => count8([
+                west(above, k),
+                above[k],
+                east(above, k),
+                west(current, k),
+                east(current, k),
+                west(below, k),
+                below[k],
+                east(below, k),
+            ]),
+        };
+        let mask = cell_mask(k, self.width);
+        self.apply(current[k], counts) & mask | current[k] & !mask
+    }
+
+    // the next 64 cells from the current ones and the bits of their
+    // neighbor counts
+    fn apply(&self, current: u64, counts: [u64; 4]) -> u64 {
+        let mut next = 0;
+        for n in 0..=8 {
+            let born = self.birth >> n & 1 != 0;
+            let survives = self.survival >> n & 1 != 0;
+            if !born && !survives {
+                continue;
+            }
+
+            let mut has_count = !0;
+            for (bit, &count) in counts.iter().enumerate() {
+                has_count &= if n >> bit & 1 != 0 { count } else { !count };
+            }
+            let keep = match (born, survives) {
+                (true, true) => !0,
+                (true, false) => !current,
+                _ => current,
+            };
+            next |= has_count & keep;
+        }
+        next
+    }
+
+    // copies the cells the topology joins to the edges into the ghost ring
+    fn fill_ghosts(&mut self) {
+        let (width, height) = (self.width as isize, self.height as isize);
+        let mut ghosts = Vec::with_capacity(2 * (width + height + 2) as usize);
+        for x in -1..=width {
+            ghosts.push((x, -1));
+            ghosts.push((x, height));
+        }
+        for y in 0..height {
+            ghosts.push((-1, y));
+            ghosts.push((width, y));
+        }
+        for (x, y) in ghosts {
+            let alive = match self.topology.map(x, y) {
+                Some((x, y)) => self.bit(y + 1, x + 1),
+                None => false,
+            };
+            self.put((y + 1) as usize, (x + 1) as usize, alive);
+        }
+    }
+
+    // the words with live cells and their row and index in it, without the
+    // ghost cells
+    fn live_words(&self) -> impl Iterator<Item = (usize, usize, u64)> + '_ {
+        (0..self.height).flat_map(move |i| {
+            (0..self.stride).filter_map(move |k| {
+                let word = self.cells[(i + 1) * self.stride + k] & cell_mask(k, self.width);
+                if word == 0 {
+                    None
+                } else {
+                    Some((i, k, word))
+                }
+            })
+        })
+    }
+
+    fn bit(&self, row: usize, column: usize) -> bool {
+        self.cells[row * self.stride + column / 64] >> (column % 64) & 1 != 0
+    }
+
+    // sets a cell in both buffers
+    fn put(&mut self, row: usize, column: usize, alive: bool) {
+        let word = row * self.stride + column / 64;
+        for cells in [&mut self.cells, &mut self.next] {
+            if alive {
+                cells[word] |= 1 << (column % 64);
+            } else {
+                cells[word] &= !(1 << (column % 64));
+            }
+        }
+    }
+}
+
+// the left neighbors of the cells in word `k` of a row
+fn west(row: &[u64], k: usize) -> u64 {
+    let carry = if k > 0 { row[k - 1] >> 63 } else { 0 };
+    row[k] << 1 | carry
+}
+
+// the right neighbors of the cells in word `k` of a row
+fn east(row: &[u64], k: usize) -> u64 {
+    let carry = if k + 1 < row.len() {
+        row[k + 1] << 63
+    } else {
+        0
+    };
+    row[k] >> 1 | carry
+}
+
+// the bits of word `k` that hold cells rather than ghosts or padding
+fn cell_mask(k: usize, width: usize) -> u64 {
+    let low = (k * 64).max(1);
+    let high = ((k + 1) * 64).min(width + 1);
+    if low >= high {
+        return 0;
+    }
+    let bits = high - low;
+    let mask = if bits == 64 { !0 } else { (1 << bits) - 1 };
+    mask << (low - k * 64)
+}
+
+fn full_add(a: u64, b: u64, c: u64) -> (u64, u64) {
+    (a ^ b ^ c, (a & b) | (c & (a ^ b)))
+}
+
+fn half_add(a: u64, b: u64) -> (u64, u64) {
+    (a ^ b, a & b)
+}
+
+// the count of eight one-bit inputs for each of 64 cells, lowest bit first
+fn count8(inputs: [u64; 8]) -> [u64; 4] {
+    let (ones_a, twos_a) = full_add(inputs[0], inputs[1], inputs[2]);
+    let (ones_b, twos_b) = full_add(inputs[3], inputs[4], inputs[5]);
+    let (ones_c, twos_c) = half_add(inputs[6], inputs[7]);
+    let (ones, twos_d) = full_add(ones_a, ones_b, ones_c);
+    let (twos_e, fours_a) = full_add(twos_a, twos_b, twos_c);
+    let (twos, fours_b) = half_add(twos_e, twos_d);
+    let (fours, eights) = half_add(fours_a, fours_b);
+    [ones, twos, fours, eights]
+}
+
+// the count of four one-bit inputs for each of 64 cells, lowest bit first
+fn count4(inputs: [u64; 4]) -> [u64; 4] {
+    let (ones_a, twos_a) = full_add(inputs[0], inputs[1], inputs[2]);
+    let (ones, twos_b) = half_add(ones_a, inputs[3]);
+    let (twos, fours) = half_add(twos_a, twos_b);
+    [ones, twos, fours, 0]
+}
+
+impl Engine for BitGrid {
+    fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    fn topology(&self) -> Option<Topology> {
+        Some(self.topology)
+    }
+
+    fn get(&self, x: i64, y: i64) -> CellState {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return CellState::DEAD;
+        }
+        BitGrid::get(self, y as usize, x as usize)
+    }
+
+    fn set(&mut self, x: i64, y: i64, state: CellState) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        BitGrid::set(self, y as usize, x as usize, state);
+    }
+
+    fn cells_in(
+        &self,
+        left: i64,
+        top: i64,
+        width: i64,
+        height: i64,
+        f: &mut dyn FnMut(i64, i64, CellState),
+    ) {
+        let cells = engine::clip(
+            self.width,
+            self.height,
+            left,
+            top,
+            left + width,
+            top + height,
+        );
+        for (i, j) in cells {
+            let state = BitGrid::get(self, i, j);
+            if state != CellState::DEAD {
+                f(j as i64, i as i64, state);
+            }
+        }
+    }
+
+    fn tick(&mut self) -> Option<&[Change]> {
+        Some(BitGrid::tick(self))
+    }
+
+    fn population(&self) -> u64 {
+        self.live_words()
+            .map(|(_, _, word)| word.count_ones() as u64)
+            .sum()
+    }
+
+    fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        let positions = self.live_words().flat_map(|(i, k, word)| {
+            (0..64)
+                .filter(move |bit| word >> bit & 1 != 0)
+                .map(move |bit| ((k * 64 + bit - 1) as i64, i as i64))
+        });
+        engine::bounding_box(positions)
+    }
+
+    fn set_threads(&mut self, threads: usize) {
+        BitGrid::set_threads(self, threads);
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::VecDeque;
+
+/// The rule number of a Wolfram rulestring such as `W30`, or `None` if it
+/// isn't one. Fails for numbers above 255.
+pub fn parse_rule(rulestring: &str) -> Option<Result<u8, String>> {
+    let number = rulestring
+        .strip_prefix('W')
+        .or_else(|| rulestring.strip_prefix('w'))?;
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(
+        number
+            .parse()
+            .map_err(|_| "Wolfram rules go from W0 to W255".to_string()),
+    )
+}
+
+/// A Wolfram elementary cellular automaton: one row of cells, each next
+/// generation computed from the three cells above it. Keeps as many
+/// generations as fit in `height`, oldest first.
+pub struct Elementary {
+    rule: u8,
+    width: usize,
+    height: usize,
+    rows: VecDeque<Vec<bool>>,
+}
+
+impl Elementary {
+    pub fn new(rule: u8, width: usize, height: usize) -> Self {
+        let mut rows = VecDeque::with_capacity(height);
+        rows.push_back(vec![false; width]);
+        Elementary {
+            rule,
+            width,
+            height,
+            rows,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn rows(&self) -> &VecDeque<Vec<bool>> {
+        &self.rows
+    }
+
+    /// Fits the automaton to a new size, keeping the cells that still fit from
+    /// the left end of the row and the newest generations.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        for row in &mut self.rows {
+            row.resize(width, false);
+        }
+        while self.rows.len() > height.max(1) {
+            self.rows.pop_front();
+        }
+        self.width = width;
+        self.height = height;
+    }
+
+    /// Flips a cell of the newest generation and returns its new value.
+    pub fn toggle(&mut self, j: usize) -> bool {
+        let row = self.rows.back_mut().unwrap();
+        row[j] = !row[j];
+        row[j]
+    }
+
+    /// Computes the next generation, wrapping around at the ends of the row.
+    /// Returns whether the oldest generation was dropped to make room.
+    pub fn step(&mut self) -> bool {
+        let current = self.rows.back().unwrap();
+        let next = (0..self.width)
+            .map(|j| {
+                let left = current[(j + self.width - 1) % self.width] as u8;
+                let center = current[j] as u8;
+                let right = current[(j + 1) % self.width] as u8;
+                self.rule & (1 << (left << 2 | center << 1 | right)) != 0
+            })
+            .collect();
+        self.rows.push_back(next);
+
+        if self.rows.len() > self.height {
+            self.rows.pop_front();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // runs a rule from a single cell in the middle of a row as wide as the
+    // rows given, and checks each generation against them
+    fn assert_rows(rule: u8, expected: &[&str]) {
+        let width = expected[0].len();
+        let mut elementary = Elementary::new(rule, width, expected.len());
+        elementary.toggle(width / 2);
+        for _ in 1..expected.len() {
+            elementary.step();
+        }
+        let rows: Vec<String> = elementary
+            .rows()
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&alive| if alive { 'o' } else { '.' })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(rows, expected, "rule {}", rule);
+    }
+
+    #[test]
+    fn rule_30_grows_its_chaotic_triangle() {
+        assert_rows(
+            30,
+            &[
+                "......o......",
+                ".....ooo.....",
+                "....oo..o....",
+                "...oo.oooo...",
+                "..oo..o...o..",
+                ".oo.oooo.ooo.",
+            ],
+        );
+    }
+
+    #[test]
+    fn rule_90_draws_a_sierpinski_triangle() {
+        assert_rows(
+            90,
+            &[
+                "......o......",
+                ".....o.o.....",
+                "....o...o....",
+                "...o.o.o.o...",
+                "..o.......o..",
+                ".o.o.....o.o.",
+            ],
+        );
+    }
+
+    #[test]
+    fn wraps_around_and_keeps_the_newest_rows() {
+        let mut elementary = Elementary::new(90, 5, 2);
+        elementary.toggle(0);
+        assert!(!elementary.step());
+        assert_eq!(elementary.rows()[1], [false, true, false, false, true]);
+        assert!(elementary.step());
+        assert_eq!(elementary.rows().len(), 2);
+        assert_eq!(elementary.rows()[1], [false, false, true, true, false]);
+    }
+
+    #[test]
+    fn parses_rule_numbers() {
+        assert_eq!(parse_rule("W30"), Some(Ok(30)));
+        assert_eq!(parse_rule("w0"), Some(Ok(0)));
+        assert_eq!(parse_rule("W255"), Some(Ok(255)));
+        assert!(matches!(parse_rule("W256"), Some(Err(_))));
+        assert!(matches!(parse_rule("W99999999999999999999"), Some(Err(_))));
+        for rulestring in &["W", "W3a", "B3/S23", "WireWorld", "30"] {
+            assert_eq!(parse_rule(rulestring), None, "{}", rulestring);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::rule::{CellState, Change, Rule};
+use crate::topology::Topology;
+
+/// A way of storing and stepping a Life-like universe. Cells are addressed
+/// by column `x` and row `y`; engines on a bounded grid read cells beyond
+/// its edges as dead and ignore writes to them.
+pub trait Engine {
+    fn rule(&self) -> &Rule;
+
+    /// The grid the cells live on, or `None` for an unbounded plane.
+    fn topology(&self) -> Option<Topology>;
+
+    fn get(&self, x: i64, y: i64) -> CellState;
+
+    fn set(&mut self, x: i64, y: i64, state: CellState);
+
+    /// Calls `f` with every cell in the given rectangle that isn't dead.
+    fn cells_in(
+        &self,
+        left: i64,
+        top: i64,
+        width: i64,
+        height: i64,
+        f: &mut dyn FnMut(i64, i64, CellState),
+    );
+
+    /// Advances `generations_per_tick()` generations and returns the cells
+    /// that changed, or `None` if the engine doesn't keep track of them.
+    fn tick(&mut self) -> Option<&[Change]>;
+
+    /// Advances `generations` generations in as few ticks as it can. By
+    /// default that is one tick per generation.
+    fn step(&mut self, generations: u64) {
+        for _ in 0..generations {
+            self.tick();
+        }
+    }
+
+    /// Whether `tick` returns the cells that changed.
+    fn tracks_changes(&self) -> bool {
+        true
+    }
+
+    /// The number of cells that aren't dead.
+    fn population(&self) -> u64;
+
+    /// The smallest rectangle holding every cell that isn't dead, as `(left,
+    /// top, width, height)`, or `None` if they are all dead.
+    fn bounding_box(&self) -> Option<(i64, i64, i64, i64)>;
+
+    fn generations_per_tick(&self) -> u64 {
+        1
+    }
+
+    /// Makes each `tick` advance 2^`exponent` generations, for engines that
+    /// can; the others always advance one.
+    fn set_step_exponent(&mut self, _exponent: u8) {}
+
+    /// Spreads each generation over this many threads, for engines that can.
+    fn set_threads(&mut self, _threads: usize) {}
+}
+
+// the rows and columns of a grid `width` by `height` cells that fall in a
+// rectangle
+pub(crate) fn clip(
+    width: usize,
+    height: usize,
+    left: i64,
+    top: i64,
+    right: i64,
+    bottom: i64,
+) -> impl Iterator<Item = (usize, usize)> {
+    let columns = left.max(0) as usize..right.clamp(0, width as i64) as usize;
+    let rows = top.max(0) as usize..bottom.clamp(0, height as i64) as usize;
+    rows.flat_map(move |i| columns.clone().map(move |j| (i, j)))
+}
+
+// the smallest rectangle holding some positions, as `(left, top, width,
+// height)`
+pub(crate) fn bounding_box(
+    positions: impl Iterator<Item = (i64, i64)>,
+) -> Option<(i64, i64, i64, i64)> {
+    positions
+        .fold(None, |bounds, (x, y)| match bounds {
+            None => Some((x, y, x, y)),
+            Some((left, top, right, bottom)) => {
+                Some((x.min(left), y.min(top), x.max(right), y.max(bottom)))
+            }
+        })
+        .map(|(left, top, right, bottom)| (left, top, right - left + 1, bottom - top + 1))
+}
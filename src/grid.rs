@@ -0,0 +1,259 @@
+use crate::bands;
+use crate::engine::{self, Engine};
+use crate::neighborhood::{self, Neighborhood};
+use crate::rule::{CellState, Change, Rule};
+use crate::tiles::Tiles;
+use crate::topology::Topology;
+
+/// A fixed-size universe whose edges are joined as given by its `Topology`.
+///
+/// Each generation is computed from `cells` into `next`, and then the two
+/// swap, stepping only the tiles `Tiles` picks.
+pub struct Grid {
+    width: usize,
+    height: usize,
+    topology: Topology,
+    rule: Rule,
+    // row-major
+    cells: Vec<CellState>,
+    next: Vec<CellState>,
+    // tiles are `TILE` cells square
+    tiles: Tiles,
+    changes: Vec<Change>,
+    threads: usize,
+}
+
+const TILE: usize = 16;
+
+impl Grid {
+    pub fn new(rule: Rule, topology: Topology) -> Self {
+        let (width, height) = (topology.width, topology.height);
+        Grid {
+            width,
+            height,
+            topology,
+            rule,
+            cells: vec![CellState::DEAD; width * height],
+            next: vec![CellState::DEAD; width * height],
+            tiles: Tiles::new(width.div_ceil(TILE), height.div_ceil(TILE)),
+            changes: Vec::new(),
+            threads: 1,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    /// Splits each generation into bands of rows stepped on this many
+    /// threads.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> CellState {
+        self.cells[i * self.width + j]
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, state: CellState) {
+        self.cells[i * self.width + j] = state;
+        self.next[i * self.width + j] = state;
+        self.mark(i, j);
+    }
+
+    fn mark(&mut self, i: usize, j: usize) {
+        self.tiles.mark(j / TILE, i / TILE);
+        if i == 0 || j == 0 || i + 1 == self.height || j + 1 == self.width {
+            self.tiles.mark_edge();
+        }
+    }
+
+    /// Advances one generation and returns the cells that changed, with
+    /// `x` the column and `y` the row.
+    pub fn tick(&mut self) -> &[Change] {
+        let width = self.width;
+        let height = self.height;
+        let counts = match self.rule.neighborhood() {
+            Neighborhood::RANGE(shape, range) => Some(neighborhood::range_counts(
+                width,
+                height,
+                shape,
+                range,
+                self.threads,
+                |x, y| self.alive(x, y),
+            )),
+            _ => None,
+        };
+
+        // counts reach further than a tile, so larger than life steps
+        // everything
+        let mut active = self.tiles.take_active();
+        let tile_columns = width.div_ceil(TILE);
+        if counts.is_some() {
+            active = (0..tile_columns * height.div_ceil(TILE)).collect();
+        }
+        let cells_in = |tile: usize| {
+            let (column, row) = (tile % tile_columns, tile / tile_columns);
+            let columns = column * TILE..((column + 1) * TILE).min(width);
+            let rows = row * TILE..((row + 1) * TILE).min(height);
+            rows.flat_map(move |i| columns.clone().map(move |j| (i, j)))
+        };
+
+        let mut next = std::mem::take(&mut self.next);
+        let grid = &*self;
+        bands::fill(
+            &mut next,
+            TILE * width,
+            self.threads,
+            bands::MIN_BAND,
+            |first_tile_row, band| {
+                let last_tile_row = first_tile_row + band.len().div_ceil(TILE * width);
+                let start = active.partition_point(|&tile| tile / tile_columns < first_tile_row);
+                let end = active.partition_point(|&tile| tile / tile_columns < last_tile_row);
+                for &tile in &active[start..end] {
+                    for (i, j) in cells_in(tile) {
+                        let neighbors = match &counts {
+                            Some(counts) => counts[i * width + j],
+                            None => grid.configuration(i, j),
+                        };
+                        band[(i - first_tile_row * TILE) * width + j] =
+                            grid.rule.next_state(grid.get(i, j), neighbors);
+                    }
+                }
+            },
+        );
+
+        self.changes.clear();
+        for &tile in &active {
+            for (i, j) in cells_in(tile) {
+                let new = next[i * width + j];
+                if self.cells[i * width + j] != new {
+                    self.changes.push(Change {
+                        x: j as i64,
+                        y: i as i64,
+                        state: new,
+                    });
+                }
+            }
+        }
+
+        self.next = std::mem::replace(&mut self.cells, next);
+        for k in 0..self.changes.len() {
+            let change = self.changes[k];
+            let (i, j) = (change.y as usize, change.x as usize);
+            self.next[i * width + j] = change.state;
+            self.mark(i, j);
+        }
+        &self.changes
+    }
+
+    // the live neighbors of a cell, one bit each
+    fn configuration(&self, i: usize, j: usize) -> usize {
+        let mut neighbors = 0;
+        let offsets = self.rule.neighborhood().offsets(i as isize);
+        let interior = 0 < i && i < self.height - 1 && 0 < j && j < self.width - 1;
+        for (bit, &(dx, dy)) in offsets.iter().enumerate() {
+            let (y, x) = (i as isize + dy, j as isize + dx);
+            let alive = if interior {
+                self.get(y as usize, x as usize) == CellState::ALIVE
+            } else {
+                self.alive(x, y)
+            };
+            if alive {
+                neighbors |= 1 << bit;
+            }
+        }
+        neighbors
+    }
+
+    // whether the cell at a position, possibly beyond the edges, is alive
+    fn alive(&self, x: isize, y: isize) -> bool {
+        match self.topology.map(x, y) {
+            Some((x, y)) => self.get(y, x) == CellState::ALIVE,
+            None => false,
+        }
+    }
+}
+
+impl Engine for Grid {
+    fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    fn topology(&self) -> Option<Topology> {
+        Some(self.topology)
+    }
+
+    fn get(&self, x: i64, y: i64) -> CellState {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return CellState::DEAD;
+        }
+        Grid::get(self, y as usize, x as usize)
+    }
+
+    fn set(&mut self, x: i64, y: i64, state: CellState) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        Grid::set(self, y as usize, x as usize, state);
+    }
+
+    fn cells_in(
+        &self,
+        left: i64,
+        top: i64,
+        width: i64,
+        height: i64,
+        f: &mut dyn FnMut(i64, i64, CellState),
+    ) {
+        let cells = engine::clip(
+            self.width,
+            self.height,
+            left,
+            top,
+            left + width,
+            top + height,
+        );
+        for (i, j) in cells {
+            let state = Grid::get(self, i, j);
+            if state != CellState::DEAD {
+                f(j as i64, i as i64, state);
+            }
+        }
+    }
+
+    fn tick(&mut self) -> Option<&[Change]> {
+        Some(Grid::tick(self))
+    }
+
+    fn population(&self) -> u64 {
+        self.cells
+            .iter()
+            .filter(|&&state| state != CellState::DEAD)
+            .count() as u64
+    }
+
+    fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        let width = self.width;
+        let positions = self.cells.iter().enumerate().filter_map(|(k, &state)| {
+            if state == CellState::DEAD {
+                None
+            } else {
+                Some(((k % width) as i64, (k / width) as i64))
+            }
+        });
+        engine::bounding_box(positions)
+    }
+
+    fn set_threads(&mut self, threads: usize) {
+        Grid::set_threads(self, threads);
+    }
+}
@@ -0,0 +1,376 @@
+use crate::rule::Change;
+use crate::universe::Universe;
+use std::collections::VecDeque;
+
+// how many generations can be stored as differences before the next snapshot,
+// which bounds how many have to be replayed to go back one
+const SNAPSHOT_INTERVAL: usize = 64;
+
+/// The past generations of a universe, so it can be taken back to any of them.
+/// Each generation is stored as the cells that changed since the one before,
+/// with a full snapshot every so often and whenever the engine doesn't say
+/// what changed. When the cells stored take up more than a given number of
+/// bytes, the oldest generations are forgotten.
+pub struct History {
+    // oldest first; the first one is always a snapshot
+    frames: VecDeque<Frame>,
+    // the frame the universe is showing
+    current: usize,
+    // bytes taken by the cells of all frames
+    size: usize,
+    capacity: usize,
+}
+
+struct Frame {
+    generation: u64,
+    // every cell that isn't dead, or the cells that changed since the frame
+    // before
+    cells: Vec<Change>,
+    snapshot: bool,
+    // cells drawn on this generation, in order
+    edits: Vec<Change>,
+}
+
+impl Frame {
+    fn size(&self) -> usize {
+        (self.cells.len() + self.edits.len()) * std::mem::size_of::<Change>()
+    }
+}
+
+impl History {
+    /// Starts with the generation the universe is at, keeping about
+    /// `capacity` bytes of cells.
+    pub fn new(universe: &Universe, capacity: usize) -> Self {
+        let mut history = History {
+            frames: VecDeque::new(),
+            current: 0,
+            size: 0,
+            capacity,
+        };
+        history.push(snapshot(universe, universe.generation()));
+        history
+    }
+
+    /// The oldest and newest generations that can be gone back to.
+    pub fn range(&self) -> (u64, u64) {
+        let newest = self.frames.back().unwrap().generation;
+        (self.frames[0].generation, newest)
+    }
+
+    /// The generation the universe was last taken to or recorded at.
+    pub fn generation(&self) -> u64 {
+        self.frames[self.current].generation
+    }
+
+    /// Whether the universe was taken back to an earlier generation.
+    pub fn rewound(&self) -> bool {
+        self.current + 1 < self.frames.len()
+    }
+
+    /// Remembers a generation the universe has just been stepped to, given
+    /// the cells that changed, if known. Stepping on from an earlier
+    /// generation forgets the ones that came after it.
+    pub fn record(&mut self, universe: &Universe, changes: Option<Vec<Change>>) {
+        self.fork();
+        let since_snapshot = self
+            .frames
+            .iter()
+            .rev()
+            .position(|frame| frame.snapshot)
+            .unwrap();
+        let generation = universe.generation();
+        match changes {
+            Some(changes)
+                if since_snapshot + 1 < SNAPSHOT_INTERVAL
+                    && (changes.len() as u64) < universe.population() =>
+            {
+                self.push(Frame {
+                    generation,
+                    cells: changes,
+                    snapshot: false,
+                    edits: Vec::new(),
+                })
+            }
+            _ => self.push(snapshot(universe, generation)),
+        }
+        self.current = self.frames.len() - 1;
+        self.trim();
+    }
+
+    /// Remembers a cell drawn on the universe. Drawing on an earlier
+    /// generation forks the timeline: the generations after it are forgotten.
+    pub fn edit(&mut self, change: Change) {
+        self.fork();
+        self.frames[self.current].edits.push(change);
+        self.size += std::mem::size_of::<Change>();
+    }
+
+    /// Takes the universe back one generation, if it can.
+    pub fn back(&mut self, universe: &mut Universe) -> bool {
+        if self.current == 0 {
+            return false;
+        }
+        self.show(universe, self.current - 1);
+        true
+    }
+
+    /// Takes the universe forward one recorded generation, if it was taken
+    /// back.
+    pub fn forward(&mut self, universe: &mut Universe) -> bool {
+        if !self.rewound() {
+            return false;
+        }
+        self.show(universe, self.current + 1);
+        true
+    }
+
+    /// Takes the universe to the last recorded generation at or before
+    /// `generation`, or the oldest one.
+    pub fn go_to(&mut self, universe: &mut Universe, generation: u64) {
+        let index = self
+            .frames
+            .partition_point(|frame| frame.generation <= generation);
+        self.show(universe, index.saturating_sub(1));
+    }
+
+    // forgets the frames after the current one
+    fn fork(&mut self) {
+        while self.rewound() {
+            let frame = self.frames.pop_back().unwrap();
+            self.size -= frame.size();
+        }
+    }
+
+    fn push(&mut self, frame: Frame) {
+        self.size += frame.size();
+        self.frames.push_back(frame);
+    }
+
+    // forgets the oldest frames, up to the next snapshot at a time, until the
+    // rest fit, keeping the ones needed to show the current frame
+    fn trim(&mut self) {
+        while self.size > self.capacity {
+            let next_snapshot = match self.frames.iter().skip(1).position(|frame| frame.snapshot) {
+                Some(position) if position < self.current => position + 1,
+                _ => return,
+            };
+            for frame in self.frames.drain(..next_snapshot) {
+                self.size -= frame.size();
+            }
+            self.current -= next_snapshot;
+        }
+    }
+
+    fn show(&mut self, universe: &mut Universe, index: usize) {
+        let replay_from = if index > self.current
+            && self
+                .frames
+                .range(self.current + 1..=index)
+                .all(|frame| !frame.snapshot)
+        {
+            self.current + 1
+        } else {
+            let snapshot = (0..=index)
+                .rev()
+                .find(|&i| self.frames[i].snapshot)
+                .unwrap();
+            universe.clear();
+            snapshot
+        };
+
+        for frame in self.frames.range(replay_from..=index) {
+            for change in frame.cells.iter().chain(&frame.edits) {
+                universe.set(change.x, change.y, change.state);
+            }
+        }
+        universe.set_generation(self.frames[index].generation);
+        self.current = index;
+    }
+}
+
+// a frame with every cell of the universe that isn't dead
+fn snapshot(universe: &Universe, generation: u64) -> Frame {
+    let mut cells = Vec::new();
+    if let Some((left, top, width, height)) = universe.bounding_box() {
+        universe.cells_in(left, top, width, height, |x, y, state| {
+            cells.push(Change { x, y, state })
+        });
+    }
+    Frame {
+        generation,
+        cells,
+        snapshot: true,
+        edits: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::CellState;
+    use crate::universe::Algorithm;
+
+    // the cells that aren't dead, in a fixed order
+    fn cells(universe: &Universe) -> Vec<(i64, i64, u8)> {
+        let mut cells = Vec::new();
+        if let Some((left, top, width, height)) = universe.bounding_box() {
+            universe.cells_in(left, top, width, height, |x, y, state| {
+                cells.push((x, y, state.0))
+            });
+        }
+        cells.sort_unstable();
+        cells
+    }
+
+    // an R-pentomino, which keeps changing for over a thousand generations
+    fn r_pentomino() -> Universe {
+        let mut universe = Universe::new(Default::default(), Algorithm::SPARSE, (0, 0)).unwrap();
+        for &(x, y) in &[(1, 0), (2, 0), (0, 1), (1, 1), (1, 2)] {
+            universe.set(x, y, CellState::ALIVE);
+        }
+        universe
+    }
+
+    // steps and records `generations` generations, returning the cells of
+    // each
+    fn run(
+        universe: &mut Universe,
+        history: &mut History,
+        generations: u64,
+    ) -> Vec<Vec<(i64, i64, u8)>> {
+        (0..generations)
+            .map(|_| {
+                let changes = universe.tick().map(|changes| changes.to_vec());
+                history.record(universe, changes);
+                cells(universe)
+            })
+            .collect()
+    }
+
+    // draws a blinker at `at`, `at`, out of the pattern's way, and returns
+    // its cells
+    fn draw_blinker(universe: &mut Universe, history: &mut History, at: i64) -> Vec<Change> {
+        let drawn: Vec<Change> = (at..at + 3)
+            .map(|x| Change {
+                x,
+                y: at,
+                state: CellState::ALIVE,
+            })
+            .collect();
+        for &change in &drawn {
+            universe.set(change.x, change.y, change.state);
+            history.edit(change);
+        }
+        drawn
+    }
+
+    #[test]
+    fn goes_back_and_forward_to_recorded_generations() {
+        let mut universe = r_pentomino();
+        let mut history = History::new(&universe, usize::MAX);
+        let mut expected = vec![cells(&universe)];
+        expected.extend(run(&mut universe, &mut history, 200));
+        assert_eq!(history.range(), (0, 200));
+
+        // across snapshots both ways, and from the newest
+        for &generation in &[0, 63, 64, 65, 200, 130, 1, 129, 128, 199] {
+            history.go_to(&mut universe, generation);
+            assert_eq!(universe.generation(), generation);
+            assert_eq!(
+                cells(&universe),
+                expected[generation as usize],
+                "{}",
+                generation
+            );
+        }
+        history.go_to(&mut universe, 62);
+        for generation in (50..62).rev() {
+            assert!(history.back(&mut universe));
+            assert_eq!(cells(&universe), expected[generation], "{}", generation);
+        }
+        for (generation, cells_then) in expected.iter().enumerate().take(80).skip(51) {
+            assert!(history.forward(&mut universe));
+            assert_eq!(&cells(&universe), cells_then, "{}", generation);
+        }
+        history.go_to(&mut universe, 0);
+        assert!(!history.back(&mut universe));
+        history.go_to(&mut universe, 500);
+        assert_eq!(universe.generation(), 200);
+        assert!(!history.forward(&mut universe));
+    }
+
+    #[test]
+    fn drawing_on_an_earlier_generation_forks() {
+        let mut universe = r_pentomino();
+        let mut history = History::new(&universe, usize::MAX);
+        let original = run(&mut universe, &mut history, 100);
+
+        history.go_to(&mut universe, 70);
+        let drawn = draw_blinker(&mut universe, &mut history, 30);
+        assert_eq!(history.range(), (0, 70));
+        let forked = run(&mut universe, &mut history, 30);
+
+        // the same edits made on a universe stepped straight to generation 70
+        let mut expected = r_pentomino();
+        expected.step(70);
+        for change in &drawn {
+            expected.set(change.x, change.y, change.state);
+        }
+        let drawn_on = cells(&expected);
+        for generation in 71..=100 {
+            expected.tick();
+            assert_eq!(forked[generation - 71], cells(&expected));
+        }
+        assert_ne!(forked[29], original[99]);
+
+        for (generation, cells_then) in [
+            (69, &original[68]),
+            (70, &drawn_on),
+            (85, &forked[85 - 71]),
+            (100, &forked[29]),
+        ] {
+            history.go_to(&mut universe, generation);
+            assert_eq!(&cells(&universe), cells_then, "{}", generation);
+        }
+    }
+
+    #[test]
+    fn trimming_keeps_the_generations_it_can_show() {
+        let mut universe = r_pentomino();
+        // room for a few runs of frames from one snapshot to the next
+        let capacity = 600_000;
+        let mut history = History::new(&universe, capacity);
+        let expected = run(&mut universe, &mut history, 1000);
+        assert!(history.size <= capacity);
+        let (oldest, newest) = history.range();
+        assert!(oldest > 0 && newest == 1000);
+        assert!(history.frames[0].snapshot);
+
+        for generation in [oldest, oldest + 1, oldest + 64, 999, 1000] {
+            history.go_to(&mut universe, generation);
+            assert_eq!(
+                cells(&universe),
+                expected[generation as usize - 1],
+                "{}",
+                generation
+            );
+        }
+        // further back than what is kept goes to the oldest
+        history.go_to(&mut universe, 0);
+        assert_eq!(universe.generation(), oldest);
+
+        // and a fork from there replays like a fresh run
+        history.go_to(&mut universe, oldest + 10);
+        let drawn = draw_blinker(&mut universe, &mut history, -40);
+        let forked = run(&mut universe, &mut history, 5);
+        let mut fresh = r_pentomino();
+        fresh.step(oldest + 10);
+        for change in &drawn {
+            fresh.set(change.x, change.y, change.state);
+        }
+        fresh.step(5);
+        assert_eq!(forked[4], cells(&fresh));
+        history.go_to(&mut universe, oldest + 12);
+        assert_eq!(cells(&universe), forked[1]);
+    }
+}
@@ -0,0 +1,28 @@
+//! Life-like cellular automata: rules in the usual rulestring notations,
+//! engines that step them, and a `Universe` that runs a rule on one of them.
+//! Has no terminal code; the `life` binary draws a `Universe` with termion.
+
+#![allow(clippy::upper_case_acronyms)]
+
+mod bands;
+pub mod bitgrid;
+pub mod elementary;
+pub mod engine;
+pub mod grid;
+pub mod hashlife;
+pub mod history;
+pub mod neighborhood;
+pub mod pattern;
+pub mod rule;
+pub mod sparse;
+mod tiles;
+pub mod topology;
+pub mod universe;
+
+pub use bands::default_threads;
+pub use engine::Engine;
+pub use history::History;
+pub use pattern::Pattern;
+pub use rule::{CellState, Change, ParseRuleError, Rule};
+pub use topology::Topology;
+pub use universe::{Algorithm, Universe};
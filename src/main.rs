@@ -1,225 +1,1699 @@
+#![allow(clippy::upper_case_acronyms)]
+
+extern crate life;
+extern crate signal_hook;
 extern crate termion;
 
-use std::io::Write;
-use std::sync::mpsc::{channel, Receiver};
+use life::elementary::{self, Elementary};
+use life::neighborhood::Neighborhood;
+use life::{Algorithm, CellState, Change, History, Pattern, Rule, Universe};
+use signal_hook::consts::{SIGINT, SIGTERM, SIGWINCH};
+use signal_hook::iterator::Signals;
+use std::collections::{HashMap, VecDeque};
+use std::io::{Stdout, Write};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, TryLockError};
+use std::time::{Duration, Instant};
 use termion::event::{Event, Key, MouseEvent};
 use termion::input::{MouseTerminal, TermRead};
-use termion::raw::IntoRawMode;
+use termion::raw::{IntoRawMode, RawTerminal};
 
 enum SimulationEvent {
     QUIT,
     PLAYPAUSE,
     DRAW(u16, u16),
+    DRAG(u16, u16),
+    PAN(i64, i64),
+    // doubles (1) or halves (-1) the number of generations per step
+    STEPSIZE(i8),
+    // goes back (-1) or forward (1) one recorded generation
+    REWIND(i8),
+    // moves along the recorded generations by a tenth of them at a time
+    SCRUB(i8),
+    // doubles (1) or halves (-1) the number of steps per second
+    SPEED(i8),
+    // advances this many generations and only draws the last one
+    STEP(u64),
+    // goes to a generation, back through the history or forward by stepping
+    JUMP(u64),
+    // the terminal changed size
+    RESIZE,
+}
+
+/// How often the simulation steps while running.
+#[derive(Clone, Copy, PartialEq)]
+enum Speed {
+    /// Steps per second, below one for a step every few seconds.
+    RATE(f64),
+    /// As fast as the universe can be stepped.
+    MAX,
+}
+
+// the slowest speed is a step every `1 / MIN_RATE` seconds, and doubling the
+// fastest one gives `MAX`
+const MIN_RATE: f64 = 1.0 / 64.0;
+const MAX_RATE: f64 = 4096.0;
+
+// how often the screen is drawn at most; steps in between are drawn together
+const FRAME_INTERVAL: Duration = Duration::from_micros(1_000_000 / 60);
+
+impl Speed {
+    fn faster(self) -> Speed {
+        match self {
+            Speed::RATE(rate) if rate * 2.0 <= MAX_RATE => Speed::RATE(rate * 2.0),
+            _ => Speed::MAX,
+        }
+    }
+
+    fn slower(self) -> Speed {
+        match self {
+            Speed::RATE(rate) => Speed::RATE((rate / 2.0).max(MIN_RATE)),
+            Speed::MAX => Speed::RATE(MAX_RATE),
+        }
+    }
+
+    // the time between steps
+    fn interval(self) -> Duration {
+        match self {
+            Speed::RATE(rate) => Duration::from_secs_f64(1.0 / rate),
+            Speed::MAX => Duration::from_secs(0),
+        }
+    }
+}
+
+impl std::str::FromStr for Speed {
+    type Err = String;
+
+    // `max`, steps per second, or `1/N` for a step every N seconds
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rate = match s.strip_prefix("1/") {
+            _ if s == "max" => return Ok(Speed::MAX),
+            Some(seconds) => seconds.parse::<f64>().map(|seconds| 1.0 / seconds),
+            None => s.parse::<f64>(),
+        };
+        match rate {
+            Ok(rate) if (MIN_RATE..=MAX_RATE).contains(&rate) => Ok(Speed::RATE(rate)),
+            _ => Err(format!(
+                "invalid speed '{}' (expected max, up to {} steps per second, or 1/N for a step every N seconds up to {})",
+                s,
+                MAX_RATE,
+                1.0 / MIN_RATE
+            )),
+        }
+    }
+}
+
+enum Automaton {
+    LIFE(Universe, History),
+    ELEMENTARY(Elementary),
+}
+
+/// What the user asked to run.
+enum Mode {
+    LIFE(Rule, Algorithm),
+    ELEMENTARY(u8),
 }
 
 struct Simulation {
     running: bool,
-    term_width: u16,
-    term_height: u16,
-    cells: Vec<Vec<Cell>>,
+    speed: Speed,
+    // when the next step is due while running
+    next_tick: Instant,
+    automaton: Automaton,
+    screen: Screen,
+    // the cells that changed since the screen was last drawn, and their
+    // latest state, or `None` if it has to be drawn from scratch
+    pending: Option<HashMap<(i64, i64), CellState>>,
+    // state the last click left a cell in, painted onto cells dragged over
+    brush: CellState,
+    // the last click on the grid
+    tap: Option<Tap>,
+    // the algorithm of a grid sized after the terminal, which is rebuilt when
+    // the terminal is resized
+    screen_grid: Option<Algorithm>,
+    threads: usize,
+    // what cells are colored by, if they are
+    coloring: Option<Coloring>,
+    // the generation on the screen, so cells that aged into another shade
+    // since are drawn again
+    shown: u64,
+    // the cells that died in the last step, and the generation it reached,
+    // to flash them when colored by changes
+    deaths: (u64, Vec<(i64, i64)>),
+    // the cells drawn as just died, which are drawn as they are next time
+    flashed: Vec<(i64, i64)>,
     input_rx: Receiver<SimulationEvent>,
 }
 
+// a click on a character, and the cell of it that the click changed
+struct Tap {
+    at: (u16, u16),
+    time: Instant,
+    // which of the character's cells, in reading order
+    cell: usize,
+    // the cell and the state it had before
+    undo: (i64, i64, CellState),
+}
+
+// how soon a click on the same character has to follow the last one to move
+// on to its next cell
+const MULTI_TAP: Duration = Duration::from_millis(600);
+
 impl Simulation {
-    fn new(input_rx: Receiver<SimulationEvent>) -> Self {
-        let (width, height) = termion::terminal_size().unwrap();
-        let mut cells = Vec::with_capacity(height as usize);
-        for _ in 0..height {
-            cells.push(Vec::with_capacity(width as usize));
-        }
-
-        for i in 0..height as usize {
-            for _ in 0..width as usize {
-                cells[i].push(Cell {
-                    old_state: CellState::DEAD,
-                    state: CellState::DEAD,
-                });
+    fn new(options: Options, input_rx: Receiver<SimulationEvent>) -> Result<Self, String> {
+        let (term_width, term_height) = termion::terminal_size().map_err(|e| e.to_string())?;
+        let mut screen_grid = None;
+        let coloring = match options.colors {
+            Colors::OFF => None,
+            _ if options.highlight => Some(Coloring::CHANGES),
+            _ => Some(Coloring::AGE),
+        };
+        let colors = escapes(options.colors, &options.palette);
+        let (automaton, screen) = match options.mode {
+            Mode::LIFE(rule, algorithm) => {
+                let mut screen =
+                    Screen::new(&rule, options.render, colors, term_width, term_height);
+                if rule.topology().is_none()
+                    && matches!(algorithm, Algorithm::NAIVE | Algorithm::BITGRID)
+                {
+                    screen_grid = Some(algorithm);
+                }
+                let mut universe = Universe::new(rule, algorithm, screen.cells())?;
+                universe.set_threads(options.threads);
+                if coloring.is_some() {
+                    universe.keep_ages();
+                }
+                if let Some(pattern) = &options.pattern {
+                    place(pattern, &mut universe)?;
+                }
+                if universe.topology().is_none() {
+                    // start with the origin in the middle of the screen
+                    let (width, height) = screen.cells();
+                    screen.origin = (-(width as i64) / 2, -(height as i64) / 2);
+                }
+                let history = History::new(&universe, options.history);
+                (Automaton::LIFE(universe, history), screen)
             }
-        }
+            Mode::ELEMENTARY(rule) => {
+                let elementary = Elementary::new(rule, term_width as usize, term_height as usize);
+                let screen = Screen::new(
+                    &Rule::default(),
+                    Render::TEXT,
+                    Vec::new(),
+                    term_width,
+                    term_height,
+                );
+                (Automaton::ELEMENTARY(elementary), screen)
+            }
+        };
 
-        Simulation {
+        Ok(Simulation {
             running: false,
-            term_width: width,
-            term_height: height,
-            cells,
+            speed: options.speed,
+            next_tick: Instant::now(),
+            automaton,
+            screen,
+            // drawn from scratch, with the pattern if one was given
+            pending: None,
+            brush: CellState::ALIVE,
+            tap: None,
+            screen_grid,
+            threads: options.threads,
+            coloring,
+            shown: 0,
+            deaths: (0, Vec::new()),
+            flashed: Vec::new(),
             input_rx,
-        }
+        })
     }
 
     fn run(&mut self) {
+        let mut next_frame = Instant::now();
         loop {
-            if self.running {
+            let now = Instant::now();
+            if self.running && now >= self.next_tick {
                 self.tick();
+                // skip the steps missed when falling behind by more than a
+                // frame instead of catching up on them all at once
+                self.next_tick += self.speed.interval();
+                if self.next_tick + FRAME_INTERVAL < now {
+                    self.next_tick = now;
+                }
+            }
+            if !self.drawn() && now >= next_frame {
+                self.render();
+                next_frame = now + FRAME_INTERVAL;
             }
 
-            for event in self.input_rx.try_iter() {
-                match event {
-                    SimulationEvent::QUIT => return,
-                    SimulationEvent::PLAYPAUSE => self.running = !self.running,
-                    SimulationEvent::DRAW(x, y) => {
-                        if self.cells[(y - 1) as usize][(x - 1) as usize].state == CellState::DEAD {
-                            self.cells[(y - 1) as usize][(x - 1) as usize].state = CellState::ALIVE;
-                            print!("{}{}", termion::cursor::Goto(x, y), 'o');
-                        } else {
-                            self.cells[(y - 1) as usize][(x - 1) as usize].state = CellState::DEAD;
-                            print!("{}{}", termion::cursor::Goto(x, y), ' ');
-                        }
+            // wait for input until the next step or frame is due, or for as
+            // long as it takes when nothing is
+            let wake = match (self.running, self.drawn()) {
+                (true, true) => Some(self.next_tick),
+                (true, false) => Some(self.next_tick.min(next_frame)),
+                (false, false) => Some(next_frame),
+                (false, true) => None,
+            };
+            let event = match wake {
+                Some(wake) => {
+                    match self
+                        .input_rx
+                        .recv_timeout(wake.saturating_duration_since(Instant::now()))
+                    {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => return,
                     }
                 }
+                None => match self.input_rx.recv() {
+                    Ok(event) => event,
+                    Err(_) => return,
+                },
+            };
+
+            let mut event = Some(event);
+            while let Some(next) = event {
+                if !self.handle(next) {
+                    return;
+                }
+                event = self.input_rx.try_recv().ok();
             }
         }
     }
 
+    // returns false to quit
+    fn handle(&mut self, event: SimulationEvent) -> bool {
+        match event {
+            SimulationEvent::QUIT => return false,
+            SimulationEvent::PLAYPAUSE => {
+                self.running = !self.running;
+                self.next_tick = Instant::now();
+            }
+            SimulationEvent::DRAW(x, y) => self.edit(x, y, false),
+            SimulationEvent::DRAG(x, y) => self.edit(x, y, true),
+            SimulationEvent::PAN(dx, dy) => self.pan(dx, dy),
+            SimulationEvent::STEPSIZE(change) => self.change_step_size(change),
+            SimulationEvent::REWIND(direction) => self.rewind(direction),
+            SimulationEvent::SCRUB(tenths) => self.scrub(tenths),
+            // handling the input that stopped a long step, if any did
+            SimulationEvent::STEP(generations) => {
+                if let Some(event) = self.step(generations) {
+                    return self.handle(event);
+                }
+            }
+            SimulationEvent::JUMP(generation) => {
+                if let Some(event) = self.jump(generation) {
+                    return self.handle(event);
+                }
+            }
+            SimulationEvent::RESIZE => {
+                // without a size, the grid stays as it is
+                if let Ok((width, height)) = termion::terminal_size() {
+                    self.resize(width, height);
+                }
+            }
+            SimulationEvent::SPEED(change) => {
+                self.speed = if change > 0 {
+                    self.speed.faster()
+                } else {
+                    self.speed.slower()
+                };
+                self.next_tick = Instant::now();
+            }
+        }
+        true
+    }
+
     fn tick(&mut self) {
-        for i in 0..self.term_height as usize {
-            for j in 0..self.term_width as usize {
-                self.cells[i][j].old_state = self.cells[i][j].state;
+        match &mut self.automaton {
+            Automaton::LIFE(universe, history) => {
+                let changes = universe.tick().map(|changes| changes.to_vec());
+                if self.coloring == Some(Coloring::CHANGES) {
+                    let deaths = changes
+                        .iter()
+                        .flatten()
+                        .filter(|change| change.state == CellState::DEAD)
+                        .map(|change| (change.x, change.y))
+                        .collect();
+                    self.deaths = (universe.generation(), deaths);
+                }
+                match (&mut self.pending, &changes) {
+                    (Some(pending), Some(changes)) => {
+                        for change in changes {
+                            pending.insert((change.x, change.y), change.state);
+                        }
+                    }
+                    _ => self.pending = None,
+                }
+                history.record(universe, changes);
+            }
+            Automaton::ELEMENTARY(elementary) => {
+                // every generation has its own row, so it is drawn right away
+                if elementary.step() {
+                    print!("{}", termion::scroll::Up(1));
+                }
+                let line = elementary.rows().len() - 1;
+                self.screen
+                    .draw_row(line, elementary.rows().back().unwrap());
+                std::io::stdout().flush().unwrap();
             }
         }
+    }
 
-        for i in 0..self.term_height as usize {
-            for j in 0..self.term_width as usize {
-                let mut neighbors = 0;
+    // returns the input that came in during a long step and stopped it
+    fn step(&mut self, generations: u64) -> Option<SimulationEvent> {
+        let mut interrupt = None;
+        match &mut self.automaton {
+            Automaton::LIFE(universe, history)
+                if universe.step_exponent() > 0 || !universe.tracks_changes() =>
+            {
+                // big steps don't say what changed, and engines that never do
+                // would record a snapshot for every generation
+                universe.step(generations);
+                history.record(universe, None);
+                self.pending = None;
+            }
+            _ => {
+                // tick by tick, so each generation is recorded, looking for
+                // input every frame so a long step can be stopped
+                let mut check = Instant::now() + FRAME_INTERVAL;
+                for _ in 0..generations {
+                    match &mut self.automaton {
+                        Automaton::LIFE(..) => self.tick(),
+                        Automaton::ELEMENTARY(elementary) => {
+                            elementary.step();
+                            self.pending = None;
+                        }
+                    }
+                    if Instant::now() >= check {
+                        check = Instant::now() + FRAME_INTERVAL;
+                        if let Ok(event) = self.input_rx.try_recv() {
+                            interrupt = Some(event);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.render();
+        interrupt
+    }
 
-                let si = i as i16;
-                let sj = j as i16;
-                let s_term_height = self.term_height as i16;
-                let s_term_width = self.term_width as i16;
-                if self.cells[(si - 1).rem_euclid(s_term_height) as usize]
-                    [(sj - 1).rem_euclid(s_term_width) as usize]
-                    .old_state
-                    == CellState::ALIVE
-                {
-                    neighbors += 1;
+    fn jump(&mut self, generation: u64) -> Option<SimulationEvent> {
+        if let Automaton::LIFE(universe, history) = &mut self.automaton {
+            let (oldest, newest) = history.range();
+            history.go_to(universe, generation.clamp(oldest, newest));
+            let behind = generation.saturating_sub(universe.generation());
+            self.running = false;
+            self.redraw();
+            return self.step(behind);
+        }
+        None
+    }
+
+    // whether the screen shows the current generation
+    fn drawn(&self) -> bool {
+        let aged = match &self.automaton {
+            Automaton::LIFE(universe, _) => {
+                self.coloring.is_some() && universe.generation() != self.shown
+            }
+            Automaton::ELEMENTARY(_) => false,
+        };
+        !aged
+            && self
+                .pending
+                .as_ref()
+                .is_some_and(|pending| pending.is_empty())
+    }
+
+    // draws the cells that changed since the last time
+    fn render(&mut self) {
+        let pending = match &mut self.pending {
+            Some(pending) => pending,
+            None => return self.redraw(),
+        };
+        match &self.automaton {
+            Automaton::LIFE(universe, _) => {
+                if let Some(coloring) = self.coloring {
+                    for (x, y) in self.flashed.drain(..) {
+                        pending.entry((x, y)).or_insert_with(|| universe.get(x, y));
+                    }
+                    // cells whose shade changed with their age alone
+                    let elapsed = universe.generation().saturating_sub(self.shown);
+                    let (width, height) = self.screen.cells();
+                    let (left, top) = self.screen.origin;
+                    let rule = universe.rule();
+                    universe.cells_in(left, top, width as i64, height as i64, |x, y, state| {
+                        let age = universe.age(x, y).unwrap_or(0);
+                        if age >= elapsed
+                            && shade(coloring, rule, state, age)
+                                != shade(coloring, rule, state, age - elapsed)
+                        {
+                            pending.entry((x, y)).or_insert(state);
+                        }
+                    });
                 }
-                if self.cells[(si - 1).rem_euclid(s_term_height) as usize][j].old_state
-                    == CellState::ALIVE
-                {
-                    neighbors += 1;
+                for ((x, y), state) in pending.drain() {
+                    let age = universe.age(x, y).unwrap_or(0);
+                    let shade = self
+                        .coloring
+                        .and_then(|coloring| shade(coloring, universe.rule(), state, age));
+                    self.screen.draw(x, y, state, shade);
                 }
-                if self.cells[(si - 1).rem_euclid(s_term_height) as usize]
-                    [(sj + 1).rem_euclid(s_term_width) as usize]
-                    .old_state
-                    == CellState::ALIVE
-                {
-                    neighbors += 1;
+                self.shown = universe.generation();
+            }
+            Automaton::ELEMENTARY(_) => {
+                for ((x, y), state) in pending.drain() {
+                    self.screen.draw(x, y, state, None);
                 }
-                if self.cells[i][(sj - 1).rem_euclid(s_term_width) as usize].old_state
-                    == CellState::ALIVE
-                {
-                    neighbors += 1;
+            }
+        }
+        self.flash_deaths();
+        std::io::stdout().flush().unwrap();
+    }
+
+    // draws the cells that died in the last step as live cells in the color
+    // of deaths, when colored by changes
+    fn flash_deaths(&mut self) {
+        if let Automaton::LIFE(universe, _) = &self.automaton {
+            let (generation, deaths) = &self.deaths;
+            if self.coloring != Some(Coloring::CHANGES) || *generation != universe.generation() {
+                return;
+            }
+            for &(x, y) in deaths {
+                if universe.get(x, y) == CellState::DEAD {
+                    self.screen.draw(x, y, CellState::ALIVE, Some(Shade::DEATH));
+                    self.flashed.push((x, y));
                 }
-                if self.cells[i][(sj + 1).rem_euclid(s_term_width) as usize].old_state
-                    == CellState::ALIVE
-                {
-                    neighbors += 1;
+            }
+        }
+    }
+
+    // a click sets the brush, dragging paints with it
+    fn edit(&mut self, x: u16, y: u16, drag: bool) {
+        match &mut self.automaton {
+            Automaton::LIFE(..) => {
+                let (across, down) = self.screen.render.block();
+                let cells = (across * down) as usize;
+                let cell = match &self.tap {
+                    // the same cell of each character as the click changed
+                    Some(tap) if drag => tap.cell % cells,
+                    _ if drag => 0,
+                    _ => self.tapped_cell(x, y, cells),
+                };
+                if cell < cells {
+                    let (x, y) = self.screen.cell_at(x, y, cell);
+                    if let Automaton::LIFE(universe, _) = &self.automaton {
+                        if !drag && universe.contains(x, y) {
+                            self.brush = universe.rule().edit(universe.get(x, y));
+                        }
+                    }
+                    self.paint(x, y, self.brush);
                 }
-                if self.cells[(si + 1).rem_euclid(s_term_height) as usize]
-                    [(sj - 1).rem_euclid(s_term_width) as usize]
-                    .old_state
-                    == CellState::ALIVE
-                {
-                    neighbors += 1;
+            }
+            Automaton::ELEMENTARY(elementary) => {
+                // only the newest generation can be edited
+                let (i, j) = ((y - 1) as usize, (x - 1) as usize);
+                if i + 1 != elementary.rows().len() || j >= elementary.width() {
+                    return;
                 }
-                if self.cells[(si + 1).rem_euclid(s_term_height) as usize][j].old_state
-                    == CellState::ALIVE
-                {
-                    neighbors += 1;
+                if !drag {
+                    elementary.toggle(j);
+                    self.brush = if elementary.rows()[i][j] {
+                        CellState::ALIVE
+                    } else {
+                        CellState::DEAD
+                    };
+                } else if elementary.rows()[i][j] != (self.brush == CellState::ALIVE) {
+                    elementary.toggle(j);
                 }
-                if self.cells[(si + 1).rem_euclid(s_term_height) as usize]
-                    [(sj + 1).rem_euclid(s_term_width) as usize]
-                    .old_state
-                    == CellState::ALIVE
-                {
-                    neighbors += 1;
-                }
-
-                if self.cells[i][j].state == CellState::ALIVE && neighbors < 2 {
-                    // die
-                    self.cells[i][j].state = CellState::DEAD;
-                    print!(
-                        "{}{}",
-                        termion::cursor::Goto((j + 1) as u16, (i + 1) as u16),
-                        ' '
-                    );
-                } else if self.cells[i][j].state == CellState::ALIVE && neighbors > 3 {
-                    // die
-                    self.cells[i][j].state = CellState::DEAD;
-                    print!(
-                        "{}{}",
-                        termion::cursor::Goto((j + 1) as u16, (i + 1) as u16),
-                        ' '
-                    );
-                } else if self.cells[i][j].state == CellState::DEAD && neighbors == 3 {
-                    // live
-                    self.cells[i][j].state = CellState::ALIVE;
-                    print!(
-                        "{}{}",
-                        termion::cursor::Goto((j + 1) as u16, (i + 1) as u16),
-                        'o'
-                    );
+                self.screen.draw(j as i64, i as i64, self.brush, None);
+            }
+        }
+        std::io::stdout().flush().unwrap();
+    }
+
+    // which cell of the character at a terminal position a click changes:
+    // the first, or when the last click was on the same character just
+    // before, the next one, taking back what that click did. Past the last
+    // cell the character is left as it was.
+    fn tapped_cell(&mut self, x: u16, y: u16, cells: usize) -> usize {
+        let mut cell = 0;
+        if let Some(tap) = self.tap.take() {
+            if tap.at == (x, y) && tap.time.elapsed() < MULTI_TAP && cells > 1 {
+                if tap.cell < cells {
+                    let (x, y, state) = tap.undo;
+                    self.paint(x, y, state);
                 }
+                cell = (tap.cell + 1) % (cells + 1);
             }
         }
+        if let Automaton::LIFE(universe, _) = &self.automaton {
+            let (cell_x, cell_y) = self.screen.cell_at(x, y, cell);
+            self.tap = Some(Tap {
+                at: (x, y),
+                time: Instant::now(),
+                cell,
+                undo: (cell_x, cell_y, universe.get(cell_x, cell_y)),
+            });
+        }
+        cell
+    }
+
+    // sets a cell, keeps the edit in the history and draws it
+    fn paint(&mut self, x: i64, y: i64, state: CellState) {
+        if let Automaton::LIFE(universe, history) = &mut self.automaton {
+            if !universe.contains(x, y) {
+                return;
+            }
+            universe.set(x, y, state);
+            history.edit(Change { x, y, state });
+            if let Some(pending) = &mut self.pending {
+                // already drawn
+                pending.remove(&(x, y));
+            }
+            let age = universe.age(x, y).unwrap_or(0);
+            let shade = self
+                .coloring
+                .and_then(|coloring| shade(coloring, universe.rule(), state, age));
+            self.screen.draw(x, y, state, shade);
+        }
+    }
+
+    fn pan(&mut self, dx: i64, dy: i64) {
+        if let Automaton::ELEMENTARY(_) = self.automaton {
+            return;
+        }
+        let (width, height) = self.screen.cells();
+        // move by an eighth of the screen
+        let step_x = (width as i64 / 8).max(1);
+        let step_y = (height as i64 / 8).max(1);
+        self.screen.origin.0 += dx * step_x;
+        self.screen.origin.1 += dy * step_y;
+        self.redraw();
+    }
 
+    // fits the screen and a grid sized after it to the terminal, leaving the
+    // cells where they were on the screen
+    fn resize(&mut self, width: u16, height: u16) {
+        self.screen.width = width;
+        self.screen.height = height;
+        match &mut self.automaton {
+            Automaton::LIFE(universe, _) => {
+                if let Some(algorithm) = self.screen_grid {
+                    let rule = universe.rule().clone();
+                    // a grid too small for the rule stays as it was
+                    if let Ok(mut resized) = Universe::new(rule, algorithm, self.screen.cells()) {
+                        resized.set_threads(self.threads);
+                        resized.set_generation(universe.generation());
+                        if self.coloring.is_some() {
+                            resized.keep_ages();
+                        }
+                        if let Some((left, top, width, height)) = universe.bounding_box() {
+                            universe.cells_in(left, top, width, height, |x, y, state| {
+                                resized.set(x, y, state)
+                            });
+                        }
+                        *universe = resized;
+                    }
+                }
+            }
+            Automaton::ELEMENTARY(elementary) => {
+                elementary.resize(width as usize, height as usize);
+            }
+        }
+        self.redraw();
+    }
+
+    fn change_step_size(&mut self, change: i8) {
+        if let Automaton::LIFE(universe, _) = &mut self.automaton {
+            let exponent = universe.step_exponent() as i8 + change;
+            universe.set_step_exponent(exponent.max(0) as u8);
+        }
+    }
+
+    fn rewind(&mut self, direction: i8) {
+        if let Automaton::LIFE(universe, history) = &mut self.automaton {
+            let moved = if direction < 0 {
+                history.back(universe)
+            } else {
+                history.forward(universe)
+            };
+            if moved {
+                self.running = false;
+                self.redraw();
+            }
+        }
+    }
+
+    fn scrub(&mut self, tenths: i8) {
+        if let Automaton::LIFE(universe, history) = &mut self.automaton {
+            let (oldest, newest) = history.range();
+            let span = ((newest - oldest) / 10).max(1) as i64;
+            let generation = history.generation() as i64 + tenths as i64 * span;
+            history.go_to(
+                universe,
+                generation.clamp(oldest as i64, newest as i64) as u64,
+            );
+            self.running = false;
+            self.redraw();
+        }
+    }
+
+    fn redraw(&mut self) {
+        self.pending = Some(HashMap::new());
+        self.flashed.clear();
+        self.screen.clear();
+        let screen = &mut self.screen;
+        let (width, height) = screen.cells();
+        let (left, top) = screen.origin;
+        match &self.automaton {
+            Automaton::LIFE(universe, _) => {
+                let coloring = self.coloring;
+                universe.cells_in(left, top, width as i64, height as i64, |x, y, state| {
+                    let age = universe.age(x, y).unwrap_or(0);
+                    let shade =
+                        coloring.and_then(|coloring| shade(coloring, universe.rule(), state, age));
+                    screen.draw(x, y, state, shade)
+                });
+                self.shown = universe.generation();
+            }
+            Automaton::ELEMENTARY(elementary) => {
+                for (i, row) in elementary.rows().iter().enumerate() {
+                    screen.draw_row(i, row);
+                }
+            }
+        }
+        self.flash_deaths();
         std::io::stdout().flush().unwrap();
     }
 }
 
-#[derive(PartialEq, Copy, Clone)]
-enum CellState {
-    ALIVE,
-    DEAD,
+/// How cells are drawn as characters.
+#[derive(Clone, Copy, PartialEq)]
+enum Render {
+    /// A character per cell, with a glyph for each state.
+    TEXT,
+    /// Two cells per character, one above the other, with half blocks.
+    HALFBLOCK,
+    /// Eight cells per character, two across and four down, with Braille
+    /// patterns.
+    BRAILLE,
+}
+
+impl Render {
+    // how many cells across and down each character shows
+    fn block(self) -> (i64, i64) {
+        match self {
+            Render::TEXT => (1, 1),
+            Render::HALFBLOCK => (1, 2),
+            Render::BRAILLE => (2, 4),
+        }
+    }
+}
+
+impl std::str::FromStr for Render {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Render::TEXT),
+            "halfblock" => Ok(Render::HALFBLOCK),
+            "braille" => Ok(Render::BRAILLE),
+            _ => Err(format!(
+                "unknown render mode '{}' (expected text, halfblock or braille)",
+                s
+            )),
+        }
+    }
+}
+
+/// How many colors cells are drawn with.
+#[derive(Clone, Copy, PartialEq)]
+enum Colors {
+    OFF,
+    /// The 16 colors every color terminal has.
+    ANSI16,
+    ANSI256,
+    /// Any color, on terminals that take 24-bit colors.
+    TRUECOLOR,
+}
+
+impl std::str::FromStr for Colors {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Colors::OFF),
+            "16" => Ok(Colors::ANSI16),
+            "256" => Ok(Colors::ANSI256),
+            "truecolor" => Ok(Colors::TRUECOLOR),
+            _ => Err(format!(
+                "unknown color mode '{}' (expected off, 16, 256 or truecolor)",
+                s
+            )),
+        }
+    }
 }
 
-struct Cell {
-    old_state: CellState,
-    state: CellState,
+/// What cells are colored by.
+#[derive(Clone, Copy, PartialEq)]
+enum Coloring {
+    /// How long ago they were born, and whether they are dying.
+    AGE,
+    /// Whether they were born or died in the last generation. Other cells
+    /// aren't colored.
+    CHANGES,
+}
+
+/// The colors of a palette, the ones for fresher cells first.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Shade {
+    NEWBORN,
+    YOUNG,
+    OLD,
+    DYING,
+    BIRTH,
+    DEATH,
+}
+
+// cells are young until they are this many generations old
+const OLD_AGE: u64 = 16;
+
+// a color for each shade, in the order of `Shade`
+type Palette = [(u8, u8, u8); 6];
+
+// the palettes by name; all but `classic` keep their colors apart for
+// colorblind people
+const PALETTES: [(&str, Palette); 4] = [
+    (
+        "classic",
+        [
+            (255, 255, 135),
+            (95, 215, 95),
+            (95, 135, 215),
+            (215, 95, 95),
+            (95, 255, 95),
+            (255, 95, 95),
+        ],
+    ),
+    // Okabe and Ito's colors for color universal design
+    (
+        "okabe-ito",
+        [
+            (240, 228, 66),
+            (86, 180, 233),
+            (0, 114, 178),
+            (213, 94, 0),
+            (86, 180, 233),
+            (230, 159, 0),
+        ],
+    ),
+    (
+        "viridis",
+        [
+            (253, 231, 37),
+            (94, 201, 98),
+            (33, 145, 140),
+            (59, 82, 139),
+            (253, 231, 37),
+            (59, 82, 139),
+        ],
+    ),
+    (
+        "grayscale",
+        [
+            (255, 255, 255),
+            (188, 188, 188),
+            (128, 128, 128),
+            (88, 88, 88),
+            (255, 255, 255),
+            (88, 88, 88),
+        ],
+    ),
+];
+
+// the usual values of the 16 colors, which terminals let users change
+const ANSI16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+// the escape codes that set the text to each color of a palette, or none
+// without colors
+fn escapes(colors: Colors, palette: &Palette) -> Vec<String> {
+    use termion::color::{AnsiValue, Fg, Rgb};
+    if colors == Colors::OFF {
+        return Vec::new();
+    }
+    palette
+        .iter()
+        .map(|&(r, g, b)| match colors {
+            Colors::ANSI16 => {
+                let color = nearest((0..16).map(|i| (i, ANSI16[i as usize])), (r, g, b));
+                // bright colors have codes of their own
+                let code = if color < 8 {
+                    30 + color
+                } else {
+                    90 + color - 8
+                };
+                format!("\x1b[{}m", code)
+            }
+            // leaving out the first 16, which themes change
+            Colors::ANSI256 => {
+                let color = nearest((16..=255).map(|i| (i, xterm_color(i))), (r, g, b));
+                Fg(AnsiValue(color)).to_string()
+            }
+            _ => Fg(Rgb(r, g, b)).to_string(),
+        })
+        .collect()
+}
+
+// the 256-color palette past the first 16: a 6 by 6 by 6 cube, then 24 grays
+fn xterm_color(index: u8) -> (u8, u8, u8) {
+    if index >= 232 {
+        let gray = 8 + 10 * (index - 232);
+        return (gray, gray, gray);
+    }
+    let level = |level: u8| if level == 0 { 0 } else { 55 + 40 * level };
+    let index = index - 16;
+    (level(index / 36), level(index / 6 % 6), level(index % 6))
+}
+
+// the index of the color closest to `color`
+fn nearest(colors: impl Iterator<Item = (u8, (u8, u8, u8))>, color: (u8, u8, u8)) -> u8 {
+    let distance = |(r, g, b): (u8, u8, u8)| {
+        let (dr, dg, db) = (
+            r as i32 - color.0 as i32,
+            g as i32 - color.1 as i32,
+            b as i32 - color.2 as i32,
+        );
+        dr * dr + dg * dg + db * db
+    };
+    colors.min_by_key(|&(_, rgb)| distance(rgb)).unwrap().0
+}
+
+// the shade of a cell that isn't dead, if it has one, given its age
+fn shade(coloring: Coloring, rule: &Rule, state: CellState, age: u64) -> Option<Shade> {
+    if state == CellState::DEAD {
+        return None;
+    }
+    if rule.is_wireworld() {
+        // by state, since the cells never die
+        return Some(match state {
+            CellState::HEAD => Shade::NEWBORN,
+            CellState::TAIL => Shade::DYING,
+            _ => Shade::OLD,
+        });
+    }
+    match coloring {
+        Coloring::AGE if state != CellState::ALIVE => Some(Shade::DYING),
+        Coloring::AGE if age == 0 => Some(Shade::NEWBORN),
+        Coloring::AGE if age < OLD_AGE => Some(Shade::YOUNG),
+        Coloring::AGE => Some(Shade::OLD),
+        Coloring::CHANGES if state == CellState::ALIVE && age == 0 => Some(Shade::BIRTH),
+        Coloring::CHANGES => None,
+    }
+}
+
+/// Where and how cells appear on the terminal.
+struct Screen {
+    // terminal size in characters
+    width: u16,
+    height: u16,
+    // the cell shown in the top left corner
+    origin: (i64, i64),
+    render: Render,
+    // glyph for each cell state
+    glyphs: Vec<char>,
+    // the escape code that sets the color of each shade, or none without
+    // colors
+    colors: Vec<String>,
+    hexagonal: bool,
+    // for each character, row by row, when characters show more than one
+    // cell: a bit for each of its cells that isn't dead, and their shades
+    dots: Vec<(u8, [Option<Shade>; 8])>,
+}
+
+impl Screen {
+    fn new(rule: &Rule, render: Render, colors: Vec<String>, width: u16, height: u16) -> Self {
+        let glyphs = (0..rule.states())
+            .map(|state| glyph(rule, CellState(state)))
+            .collect();
+        Screen {
+            width,
+            height,
+            origin: (0, 0),
+            render,
+            glyphs,
+            colors,
+            hexagonal: rule.neighborhood() == Neighborhood::HEXAGONAL,
+            dots: vec![(0, [None; 8]); width as usize * height as usize],
+        }
+    }
+
+    // how many columns and rows of cells fit on the terminal
+    fn cells(&self) -> (usize, usize) {
+        let (across, down) = self.render.block();
+        if self.hexagonal {
+            // two columns per cell plus the shift of odd rows
+            (
+                (self.width as usize).saturating_sub(1) / 2,
+                self.height as usize,
+            )
+        } else {
+            (
+                self.width as usize * across as usize,
+                self.height as usize * down as usize,
+            )
+        }
+    }
+
+    // blanks the terminal
+    fn clear(&mut self) {
+        print!("{}", termion::clear::All);
+        self.dots = vec![(0, [None; 8]); self.width as usize * self.height as usize];
+    }
+
+    // draws a cell if it is in view, in the color of a shade
+    fn draw(&mut self, x: i64, y: i64, state: CellState, shade: Option<Shade>) {
+        let row = y - self.origin.1;
+        let mut column = x - self.origin.0;
+        if self.render != Render::TEXT {
+            return self.draw_dot(column, row, state, shade);
+        }
+        if self.hexagonal {
+            column = 2 * column + y.rem_euclid(2);
+        }
+        if row < 0 || column < 0 || row >= self.height as i64 || column >= self.width as i64 {
+            return;
+        }
+        self.print(
+            column as usize,
+            row as usize,
+            self.glyphs[state.0 as usize],
+            shade,
+        );
+    }
+
+    // sets or clears the dot for a cell and draws its character again, given
+    // the cell's position from the top left corner of the screen
+    fn draw_dot(&mut self, column: i64, row: i64, state: CellState, shade: Option<Shade>) {
+        let (across, down) = self.render.block();
+        if row < 0
+            || column < 0
+            || row >= self.height as i64 * down
+            || column >= self.width as i64 * across
+        {
+            return;
+        }
+        let (i, j) = ((row / down) as usize, (column / across) as usize);
+        let (dx, dy) = (column % across, row % down);
+        let dot = match self.render {
+            Render::BRAILLE if dy == 3 => 6 + dx,
+            // Braille numbers the first three dots of each column downwards
+            Render::BRAILLE => 3 * dx + dy,
+            _ => dy,
+        } as usize;
+        let (dots, shades) = &mut self.dots[i * self.width as usize + j];
+        if state == CellState::DEAD {
+            *dots &= !(1 << dot);
+            shades[dot] = None;
+        } else {
+            *dots |= 1 << dot;
+            shades[dot] = shade;
+        }
+        // the character takes the shade of its freshest cell
+        let (dots, shade) = (*dots, shades.iter().flatten().min().copied());
+        let glyph = match (self.render, dots) {
+            (_, 0) => ' ',
+            (Render::HALFBLOCK, dots) => HALF_BLOCKS[dots as usize],
+            (_, dots) => std::char::from_u32(BRAILLE_BLANK + dots as u32).unwrap(),
+        };
+        self.print(j, i, glyph, shade);
+    }
+
+    // prints a glyph at a column and row of the terminal, counted from 0
+    fn print(&self, column: usize, row: usize, glyph: char, shade: Option<Shade>) {
+        let goto = termion::cursor::Goto((column + 1) as u16, (row + 1) as u16);
+        match shade.and_then(|shade| self.colors.get(shade as usize)) {
+            Some(color) => print!(
+                "{}{}{}{}",
+                goto,
+                color,
+                glyph,
+                termion::color::Fg(termion::color::Reset)
+            ),
+            None => print!("{}{}", goto, glyph),
+        }
+    }
+
+    fn draw_row(&self, i: usize, row: &[bool]) {
+        let line: String = row
+            .iter()
+            .map(|&alive| self.glyphs[alive as usize])
+            .collect();
+        print!("{}{}", termion::cursor::Goto(1, (i + 1) as u16), line);
+    }
+
+    // the cell drawn at a terminal position, or the `cell`th in reading order
+    // of a character showing more than one
+    fn cell_at(&self, x: u16, y: u16, cell: usize) -> (i64, i64) {
+        let (across, down) = self.render.block();
+        let (dx, dy) = (cell as i64 % across, cell as i64 / across);
+        let row = (y - 1) as i64 * down + dy + self.origin.1;
+        let mut column = (x - 1) as i64 * across + dx;
+        if self.hexagonal {
+            column = (column - row.rem_euclid(2)).div_euclid(2);
+        }
+        (column + self.origin.0, row)
+    }
+}
+
+// the top half, bottom half and both, by which cells aren't dead
+const HALF_BLOCKS: [char; 4] = [' ', '▀', '▄', '█'];
+
+// the Braille pattern without dots, the dots being the bits above it
+const BRAILLE_BLANK: u32 = 0x2800;
+
+fn glyph(rule: &Rule, state: CellState) -> char {
+    if rule.is_wireworld() {
+        match state {
+            CellState::HEAD => '@',
+            CellState::TAIL => '*',
+            CellState::CONDUCTOR => '#',
+            _ => ' ',
+        }
+    } else if state == CellState::DEAD {
+        ' '
+    } else if state == CellState::ALIVE {
+        'o'
+    } else {
+        // spread the dying states evenly over the fading glyphs
+        let dying = (state.0 - 2) as usize;
+        let span = (rule.states() - 2) as usize;
+        DYING_GLYPHS[dying * DYING_GLYPHS.len() / span]
+    }
+}
+
+const DYING_GLYPHS: [char; 3] = ['+', ':', '.'];
+
+struct Options {
+    mode: Mode,
+    threads: usize,
+    // bytes of past generations to keep
+    history: usize,
+    speed: Speed,
+    render: Render,
+    colors: Colors,
+    palette: Palette,
+    // color births and deaths instead of ages
+    highlight: bool,
+    pattern: Option<Pattern>,
+    headless: bool,
+    generations: Option<u64>,
+    until: Option<Until>,
+    // where the headless run writes its result, or stdout
+    output: Option<String>,
+}
+
+/// When a headless run stops before its number of generations.
+#[derive(Clone, Copy)]
+enum Until {
+    /// Every cell died.
+    EMPTY,
+    /// A generation was the same as the one before.
+    STILL,
+    /// A generation was the same as an earlier one, possibly moved.
+    PERIODIC,
+}
+
+impl std::str::FromStr for Until {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "empty" => Ok(Until::EMPTY),
+            "still" => Ok(Until::STILL),
+            "periodic" => Ok(Until::PERIODIC),
+            _ => Err(format!(
+                "unknown stop condition '{}' (expected empty, still or periodic)",
+                s
+            )),
+        }
+    }
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut rulestring = None;
+    let mut neighborhood = None;
+    let mut algorithm = None;
+    let mut threads = life::default_threads();
+    let mut history = 64 << 20;
+    let mut speed = Speed::RATE(32.0);
+    let mut render = Render::TEXT;
+    let mut colors = None;
+    let mut palette = None;
+    let mut highlight = false;
+    let mut pattern = None;
+    let mut headless = false;
+    let mut generations = None;
+    let mut until = None;
+    let mut output = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-n" | "--neighborhood" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                neighborhood = Some(value.parse::<Neighborhood>()?);
+            }
+            "-a" | "--algorithm" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                algorithm = Some(value.parse()?);
+            }
+            "-t" | "--threads" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                threads = match value.parse() {
+                    Ok(threads) if threads > 0 => threads,
+                    _ => return Err(format!("'{}' is not a number of threads", value)),
+                };
+            }
+            "--history" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                history = match value.parse::<usize>() {
+                    Ok(megabytes) => megabytes << 20,
+                    _ => return Err(format!("'{}' is not a number of megabytes", value)),
+                };
+            }
+            "-s" | "--speed" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                speed = value.parse()?;
+            }
+            "-r" | "--render" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                render = value.parse()?;
+            }
+            "-c" | "--color" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                colors = Some(value.parse()?);
+            }
+            "--palette" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                palette = match PALETTES.iter().find(|&&(name, _)| name == value) {
+                    Some(&(_, palette)) => Some(palette),
+                    None => {
+                        let names: Vec<_> = PALETTES.iter().map(|&(name, _)| name).collect();
+                        return Err(format!(
+                            "unknown palette '{}' (expected {})",
+                            value,
+                            names.join(", ")
+                        ));
+                    }
+                };
+            }
+            "--highlight" => highlight = true,
+            "-p" | "--pattern" => {
+                let path = args.next().ok_or(format!("{} needs a value", arg))?;
+                pattern = Some(read_pattern(&path)?);
+            }
+            "--headless" => headless = true,
+            "-g" | "--generations" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                generations = match value.parse() {
+                    Ok(generations) => Some(generations),
+                    _ => return Err(format!("'{}' is not a number of generations", value)),
+                };
+            }
+            "--until" => {
+                let value = args.next().ok_or(format!("{} needs a value", arg))?;
+                until = Some(value.parse()?);
+            }
+            "-o" | "--output" => {
+                output = Some(args.next().ok_or(format!("{} needs a value", arg))?);
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ if rulestring.is_none() => rulestring = Some(arg),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    let rulestring = rulestring
+        .or_else(|| {
+            pattern
+                .as_ref()
+                .and_then(|pattern: &Pattern| pattern.rule.clone())
+        })
+        .unwrap_or_else(|| Rule::default().to_string());
+    let mode = match elementary::parse_rule(&rulestring) {
+        Some(number) => {
+            Mode::ELEMENTARY(number.map_err(|e| format!("invalid rule '{}': {}", rulestring, e))?)
+        }
+        None => {
+            let rule = Rule::parse_with_neighborhood(&rulestring, neighborhood)
+                .map_err(|e| format!("invalid rule '{}': {}", rulestring, e))?;
+            let algorithm = match algorithm {
+                Some(algorithm) => algorithm,
+                // there is no terminal to size a grid after
+                None if headless && rule.topology().is_none() => {
+                    Algorithm::default_unbounded(&rule)
+                }
+                None => Algorithm::default_for(&rule),
+            };
+            Mode::LIFE(rule, algorithm)
+        }
+    };
+    // asking for a palette or highlights asks for colors
+    let colors = colors.unwrap_or(if palette.is_some() || highlight {
+        Colors::ANSI256
+    } else {
+        Colors::OFF
+    });
+    if colors != Colors::OFF {
+        if let Mode::ELEMENTARY(_) = mode {
+            return Err("Wolfram rules are drawn without colors".to_string());
+        }
+    }
+    if render != Render::TEXT {
+        // these draw a generation per line and shift every other row
+        match &mode {
+            Mode::ELEMENTARY(_) => return Err("Wolfram rules are only drawn as text".to_string()),
+            Mode::LIFE(rule, _) if rule.neighborhood() == Neighborhood::HEXAGONAL => {
+                return Err("hexagonal rules are only drawn as text".to_string())
+            }
+            Mode::LIFE(..) => {}
+        }
+    }
+
+    Ok(Options {
+        mode,
+        threads,
+        history,
+        speed,
+        render,
+        colors,
+        palette: palette.unwrap_or(PALETTES[0].1),
+        highlight,
+        pattern,
+        headless,
+        generations,
+        until,
+        output,
+    })
+}
+
+// reads a pattern file, or stdin for `-`
+fn read_pattern(path: &str) -> Result<Pattern, String> {
+    let text = if path == "-" {
+        let mut text = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut text).map(|_| text)
+    } else {
+        std::fs::read_to_string(path)
+    };
+    text.map_err(|e| format!("can't read '{}': {}", path, e))?
+        .parse()
+        .map_err(|e| format!("invalid pattern '{}': {}", path, e))
+}
+
+// puts a pattern in the middle of a grid, or around the origin of a plane
+fn place(pattern: &Pattern, universe: &mut Universe) -> Result<(), String> {
+    let (left, top) = match universe.topology() {
+        Some(topology) => (
+            (topology.width as i64 - pattern.width) / 2,
+            (topology.height as i64 - pattern.height) / 2,
+        ),
+        None => (-pattern.width / 2, -pattern.height / 2),
+    };
+    pattern.place(universe, left, top)
+}
+
+// runs without a terminal and writes the last generation as RLE, with how
+// it got there in comments
+fn run_headless(options: Options) -> Result<(), String> {
+    let (rule, algorithm) = match options.mode {
+        Mode::LIFE(rule, algorithm) => (rule, algorithm),
+        Mode::ELEMENTARY(_) => return Err("Wolfram rules can't run headless".to_string()),
+    };
+    if options.generations.is_none() && options.until.is_none() {
+        return Err("--headless needs --generations or --until".to_string());
+    }
+    if rule.topology().is_none() && matches!(algorithm, Algorithm::NAIVE | Algorithm::BITGRID) {
+        return Err(format!(
+            "{} needs a bounded grid suffix such as ':T100,100' to run headless",
+            rule
+        ));
+    }
+
+    let mut universe = Universe::new(rule, algorithm, (0, 0))?;
+    universe.set_threads(options.threads);
+    if let Some(pattern) = &options.pattern {
+        place(pattern, &mut universe)?;
+    }
+
+    let limit = options.generations.unwrap_or(u64::MAX);
+    let reason = match options.until {
+        None => {
+            universe.step(limit);
+            format!("after {} generations", limit)
+        }
+        Some(until) => {
+            // the cells of the last generation, for engines that don't say
+            // what changed
+            let mut previous = match until {
+                Until::STILL if !universe.tracks_changes() => cells(&universe),
+                _ => Vec::new(),
+            };
+            // the generation each shape, moved to the same corner, was last
+            // seen in, by fingerprint, and the fingerprints in the order they
+            // were seen, to forget them after `LONGEST_PERIOD` generations
+            let mut seen: HashMap<u64, (u64, Shape)> = HashMap::new();
+            let mut order = VecDeque::new();
+            if let Until::PERIODIC = until {
+                let shape = Pattern::from_universe(&universe).cells;
+                order.push_back(fingerprint(&shape));
+                seen.insert(fingerprint(&shape), (universe.generation(), shape));
+            }
+            loop {
+                if universe.generation() >= limit {
+                    break format!("after {} generations", limit);
+                }
+                match until {
+                    Until::EMPTY => {
+                        universe.step(1);
+                        if universe.population() == 0 {
+                            break "empty".to_string();
+                        }
+                    }
+                    Until::STILL => {
+                        let still = match universe.tick() {
+                            Some(changes) => changes.is_empty(),
+                            None => {
+                                let current = cells(&universe);
+                                let still = current == previous;
+                                previous = current;
+                                still
+                            }
+                        };
+                        if still {
+                            break "still".to_string();
+                        }
+                    }
+                    Until::PERIODIC => {
+                        universe.step(1);
+                        let generation = universe.generation();
+                        let shape = Pattern::from_universe(&universe).cells;
+                        let key = fingerprint(&shape);
+                        if let Some((earlier, earlier_shape)) = seen.get(&key) {
+                            // a fingerprint can be shared by different shapes
+                            if *earlier_shape == shape {
+                                break format!("periodic with period {}", generation - earlier);
+                            }
+                        }
+                        seen.insert(key, (generation, shape));
+                        order.push_back(key);
+                        if order.len() as u64 > LONGEST_PERIOD {
+                            let oldest = order.pop_front().unwrap();
+                            let expired = generation - LONGEST_PERIOD;
+                            if seen
+                                .get(&oldest)
+                                .is_some_and(|&(seen_at, _)| seen_at <= expired)
+                            {
+                                seen.remove(&oldest);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    let mut text = format!(
+        "#C generation: {}\n#C population: {}\n",
+        universe.generation(),
+        universe.population()
+    );
+    if let Some((left, top, width, height)) = universe.bounding_box() {
+        text += &format!(
+            "#C bounding box: {} by {} at {}, {}\n",
+            width, height, left, top
+        );
+    }
+    text += &format!("#C stopped: {}\n", reason);
+    text += &Pattern::from_universe(&universe).to_string();
+
+    match &options.output {
+        Some(path) => {
+            std::fs::write(path, text).map_err(|e| format!("can't write '{}': {}", path, e))
+        }
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+// the cells that aren't dead, from the top left corner of their bounding
+// box or from the origin
+type Shape = Vec<(i64, i64, CellState)>;
+
+// the longest period `--until periodic` finds, which bounds the shapes it
+// keeps to compare with
+const LONGEST_PERIOD: u64 = 10_000;
+
+// the cells that aren't dead, where they are, in a fixed order
+fn cells(universe: &Universe) -> Shape {
+    let mut cells = Vec::new();
+    if let Some((left, top, width, height)) = universe.bounding_box() {
+        universe.cells_in(left, top, width, height, |x, y, state| {
+            cells.push((x, y, state))
+        });
+    }
+    cells.sort_unstable_by_key(|&(x, y, _)| (y, x));
+    cells
+}
+
+// a hash of a shape, which doesn't depend on the order of its cells
+fn fingerprint(shape: &Shape) -> u64 {
+    shape.iter().fold(0u64, |sum, &(x, y, state)| {
+        let cell = mix(mix(x as u64) ^ y as u64) ^ state.0 as u64;
+        sum.wrapping_add(mix(cell))
+    })
+}
+
+// splitmix64's finalizer, so nearby cells hash far apart
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// the terminal while the program has taken it over, where the panic hook and
+// signal handler can put it back from
+static TERMINAL: Mutex<Option<MouseTerminal<RawTerminal<Stdout>>>> = Mutex::new(None);
+
+/// Takes over the terminal: raw mode, mouse reporting, and the alternate
+/// screen with the cursor hidden. Puts it back as it was when dropped, when
+/// anything panics, and on SIGINT or SIGTERM.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("can't set up the terminal: {}", e);
+        let mut terminal = MouseTerminal::from(std::io::stdout().into_raw_mode().map_err(error)?);
+        write!(
+            terminal,
+            "{}{}{}",
+            termion::screen::ToAlternateScreen,
+            termion::clear::All,
+            termion::cursor::Hide
+        )
+        .and_then(|_| terminal.flush())
+        .map_err(error)?;
+        *TERMINAL.lock().unwrap() = Some(terminal);
+
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            // first, so the message is printed on the main screen
+            restore_terminal();
+            default_hook(info);
+            // a panic in the input thread would otherwise leave the program
+            // running without anything reading keys
+            std::process::exit(101);
+        }));
+
+        let mut signals = Signals::new([SIGINT, SIGTERM]).map_err(error)?;
+        std::thread::spawn(move || {
+            if let Some(signal) = signals.forever().next() {
+                // keeps the simulation from drawing over the main screen
+                let _stdout = std::io::stdout().lock();
+                restore_terminal();
+                std::process::exit(128 + signal);
+            }
+        });
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+fn restore_terminal() {
+    let mut terminal = match TERMINAL.try_lock() {
+        Ok(terminal) => terminal,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        // being put back by another thread
+        Err(TryLockError::WouldBlock) => return,
+    };
+    if let Some(mut terminal) = terminal.take() {
+        // errors are ignored, there is nowhere left to report them
+        let _ = write!(
+            terminal,
+            "{}{}",
+            termion::screen::ToMainScreen,
+            termion::cursor::Show
+        );
+        // dropping it turns mouse reporting and raw mode off
+        drop(terminal);
+        let _ = std::io::stdout().flush();
+    }
 }
 
 fn main() {
-    let mut stdout = MouseTerminal::from(std::io::stdout().into_raw_mode().unwrap());
-
-    write!(
-        stdout,
-        "{}{}{}",
-        termion::screen::ToAlternateScreen,
-        termion::clear::All,
-        termion::cursor::Hide
-    )
-    .unwrap();
-    stdout.flush().unwrap();
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("life: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if options.headless {
+        if let Err(e) = run_headless(options) {
+            eprintln!("life: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
     let (event_tx, event_rx) = channel();
-    let mut simulation = Simulation::new(event_rx);
+    let resize_tx = event_tx.clone();
+    let mut simulation = match Simulation::new(options, event_rx) {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            eprintln!("life: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // keys come from the terminal itself, stdin may be the pattern
+    let tty = match termion::get_tty() {
+        Ok(tty) => tty,
+        Err(e) => {
+            eprintln!("life: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let _guard = match TerminalGuard::new() {
+        Ok(guard) => guard,
+        Err(e) => {
+            eprintln!("life: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut signals = Signals::new([SIGWINCH]).unwrap();
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            if resize_tx.send(SimulationEvent::RESIZE).is_err() {
+                break;
+            }
+        }
+    });
 
     std::thread::spawn(move || {
-        let stdin = std::io::stdin();
-        for event in stdin.events() {
-            let event = event.unwrap();
+        // the number typed before a key, such as the 100 in `100n`
+        let mut count: Option<u64> = None;
+        for event in tty.events() {
+            let event = match event {
+                Ok(event) => event,
+                Err(_) => break,
+            };
+            if let Event::Key(Key::Char(digit @ '0'..='9')) = event {
+                let digit = digit.to_digit(10).unwrap() as u64;
+                count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                continue;
+            }
+            let count = count.take();
             match event {
                 Event::Key(Key::Char(' ')) => {
                     // toggle simulation pause/resume
                     event_tx.send(SimulationEvent::PLAYPAUSE).unwrap();
                 }
 
-                Event::Mouse(MouseEvent::Press(_, x, y)) | Event::Mouse(MouseEvent::Hold(x, y)) => {
+                Event::Mouse(MouseEvent::Press(_, x, y)) => {
                     event_tx.send(SimulationEvent::DRAW(x, y)).unwrap();
                 }
 
-                Event::Key(Key::Char('q')) | Event::Key(Key::Esc) => {
+                Event::Mouse(MouseEvent::Hold(x, y)) => {
+                    event_tx.send(SimulationEvent::DRAG(x, y)).unwrap();
+                }
+
+                Event::Key(Key::Left) => event_tx.send(SimulationEvent::PAN(-1, 0)).unwrap(),
+                Event::Key(Key::Right) => event_tx.send(SimulationEvent::PAN(1, 0)).unwrap(),
+                Event::Key(Key::Up) => event_tx.send(SimulationEvent::PAN(0, -1)).unwrap(),
+                Event::Key(Key::Down) => event_tx.send(SimulationEvent::PAN(0, 1)).unwrap(),
+
+                Event::Key(Key::Char(']')) => event_tx.send(SimulationEvent::STEPSIZE(1)).unwrap(),
+                Event::Key(Key::Char('[')) => event_tx.send(SimulationEvent::STEPSIZE(-1)).unwrap(),
+
+                Event::Key(Key::Char(',')) => event_tx.send(SimulationEvent::REWIND(-1)).unwrap(),
+                Event::Key(Key::Char('.')) => event_tx.send(SimulationEvent::REWIND(1)).unwrap(),
+                Event::Key(Key::Char('<')) => event_tx.send(SimulationEvent::SCRUB(-1)).unwrap(),
+                Event::Key(Key::Char('>')) => event_tx.send(SimulationEvent::SCRUB(1)).unwrap(),
+                Event::Key(Key::Char('+')) | Event::Key(Key::Char('=')) => {
+                    event_tx.send(SimulationEvent::SPEED(1)).unwrap()
+                }
+                Event::Key(Key::Char('-')) => event_tx.send(SimulationEvent::SPEED(-1)).unwrap(),
+
+                Event::Key(Key::Char('n')) => event_tx
+                    .send(SimulationEvent::STEP(count.unwrap_or(1)))
+                    .unwrap(),
+                Event::Key(Key::Char('g')) => event_tx
+                    .send(SimulationEvent::JUMP(count.unwrap_or(0)))
+                    .unwrap(),
+
+                Event::Key(Key::Home) => event_tx.send(SimulationEvent::SCRUB(-10)).unwrap(),
+                Event::Key(Key::End) => event_tx.send(SimulationEvent::SCRUB(10)).unwrap(),
+
+                // raw mode turns ctrl-c into a key instead of SIGINT
+                Event::Key(Key::Char('q')) | Event::Key(Key::Esc) | Event::Key(Key::Ctrl('c')) => {
                     event_tx.send(SimulationEvent::QUIT).unwrap();
                     break;
                 }
 
                 _ => {}
             }
-
-            stdout.flush().unwrap();
         }
+        // without keys there is no way to quit, so quit now
+        let _ = event_tx.send(SimulationEvent::QUIT);
     });
 
     simulation.run();
-
-    print!("{}{}", termion::screen::ToMainScreen, termion::cursor::Show);
 }
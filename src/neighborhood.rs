@@ -0,0 +1,159 @@
+use crate::bands;
+
+/// Offsets `(dx, dy)` of the Moore neighbors. Neighbor `i` is bit `i` of a
+/// neighborhood configuration.
+pub const NEIGHBORS: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+const VON_NEUMANN_NEIGHBORS: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+// Hexagonal grids are drawn with odd rows shifted half a cell to the right,
+// so which cells of the rows above and below touch depends on the row.
+const HEXAGONAL_EVEN_NEIGHBORS: [(isize, isize); 6] =
+    [(-1, -1), (0, -1), (-1, 0), (1, 0), (-1, 1), (0, 1)];
+const HEXAGONAL_ODD_NEIGHBORS: [(isize, isize); 6] =
+    [(0, -1), (1, -1), (-1, 0), (1, 0), (0, 1), (1, 1)];
+
+/// How a rule looks at the cells around the one being updated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Neighborhood {
+    /// The eight cells of `NEIGHBORS`, passed to the rule as a bit per cell.
+    MOORE,
+    /// The four orthogonal neighbors, passed to the rule as a bit per cell.
+    VONNEUMANN,
+    /// The six neighbors on a hexagonal grid, passed to the rule as a bit per
+    /// cell.
+    HEXAGONAL,
+    /// Every cell of `Shape` out to the given range, passed to the rule as
+    /// the number of live cells (Larger than Life).
+    RANGE(Shape, usize),
+}
+
+impl Neighborhood {
+    /// Offsets of the neighbors of a cell in row `y`, in the order of their
+    /// bits in a configuration. `RANGE` neighborhoods are counted instead and
+    /// have no offsets.
+    pub fn offsets(self, y: isize) -> &'static [(isize, isize)] {
+        match self {
+            Neighborhood::MOORE => &NEIGHBORS,
+            Neighborhood::VONNEUMANN => &VON_NEUMANN_NEIGHBORS,
+            Neighborhood::HEXAGONAL if y.rem_euclid(2) == 0 => &HEXAGONAL_EVEN_NEIGHBORS,
+            Neighborhood::HEXAGONAL => &HEXAGONAL_ODD_NEIGHBORS,
+            Neighborhood::RANGE(..) => &[],
+        }
+    }
+}
+
+impl std::str::FromStr for Neighborhood {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "moore" => Ok(Neighborhood::MOORE),
+            "vonneumann" => Ok(Neighborhood::VONNEUMANN),
+            "hexagonal" | "hex" => Ok(Neighborhood::HEXAGONAL),
+            _ => Err(format!(
+                "unknown neighborhood '{}' (expected moore, vonneumann or hexagonal)",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    MOORE,
+    VONNEUMANN,
+    /// Cells whose distance from the center is less than `range + 0.5`.
+    CIRCULAR,
+}
+
+impl Shape {
+    /// How far the shape reaches left and right on each row, for rows
+    /// `-range..=range` relative to the center.
+    pub fn widths(self, range: usize) -> Vec<usize> {
+        let range = range as isize;
+        (-range..=range)
+            .map(|dy| match self {
+                Shape::MOORE => range as usize,
+                Shape::VONNEUMANN => (range - dy.abs()) as usize,
+                Shape::CIRCULAR => {
+                    let limit = range * range + range - dy * dy;
+                    (0..=range).take_while(|dx| dx * dx <= limit).count() - 1
+                }
+            })
+            .collect()
+    }
+
+    /// Number of cells in the shape, including the center.
+    pub fn size(self, range: usize) -> usize {
+        self.widths(range).iter().map(|w| 2 * w + 1).sum()
+    }
+
+    /// Offsets `(dx, dy)` of every cell in the shape except the center.
+    pub fn offsets(self, range: usize) -> Vec<(isize, isize)> {
+        let range = range as isize;
+        let mut offsets = Vec::new();
+        for (dy, w) in (-range..=range).zip(self.widths(range as usize)) {
+            let w = w as isize;
+            offsets.extend((-w..=w).map(|dx| (dx, dy)).filter(|&o| o != (0, 0)));
+        }
+        offsets
+    }
+}
+
+/// Counts the live cells of `shape` around every cell of a `width` by
+/// `height` grid, not counting the cell itself. `alive` is asked about every
+/// position within `range` of the grid, so it decides what lies beyond the
+/// edges.
+///
+/// Each row of that padded grid gets a prefix sum, which makes a row of the
+/// shape a single subtraction. The counting is split over `threads`.
+pub fn range_counts(
+    width: usize,
+    height: usize,
+    shape: Shape,
+    range: usize,
+    threads: usize,
+    alive: impl Fn(isize, isize) -> bool + Sync,
+) -> Vec<usize> {
+    let padded_width = width + 2 * range;
+    let padded_height = height + 2 * range;
+    let mut prefix = vec![0; padded_height * (padded_width + 1)];
+    for k in 0..padded_height {
+        let row = &mut prefix[k * (padded_width + 1)..(k + 1) * (padded_width + 1)];
+        let y = k as isize - range as isize;
+        for l in 0..padded_width {
+            let x = l as isize - range as isize;
+            row[l + 1] = row[l] + alive(x, y) as usize;
+        }
+    }
+
+    let widths = shape.widths(range);
+    let mut counts = vec![0; width * height];
+    bands::fill(
+        &mut counts,
+        width,
+        threads,
+        bands::MIN_BAND,
+        |first_row, band| {
+            for (k, count) in band.iter_mut().enumerate() {
+                let (y, x) = (first_row + k / width, k % width);
+                for (dy, &w) in widths.iter().enumerate() {
+                    let row = &prefix[(y + dy) * (padded_width + 1)..];
+                    *count += row[x + range + w + 1] - row[x + range - w];
+                }
+                *count -= alive(x as isize, y as isize) as usize;
+            }
+        },
+    );
+    counts
+}
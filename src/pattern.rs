@@ -0,0 +1,285 @@
+use crate::rule::CellState;
+use crate::universe::Universe;
+use std::fmt;
+use std::str::FromStr;
+
+// the longest line written in RLE, as Golly does
+const RLE_LINE_LENGTH: usize = 70;
+// the longest run read from RLE, so a count can't ask for more cells than
+// fit in memory
+const MAX_RUN: i64 = 1 << 20;
+
+/// A pattern as stored in a file: the cells that aren't dead, counted from
+/// the top left corner of the pattern, and the rule the file names, if any.
+///
+/// Reads Golly's run-length encoded format (RLE) and plaintext (`.cells`)
+/// files, and displays as RLE.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pattern {
+    pub width: i64,
+    pub height: i64,
+    pub cells: Vec<(i64, i64, CellState)>,
+    pub rule: Option<String>,
+}
+
+impl Pattern {
+    /// The cells of a universe that aren't dead, from the top left corner of
+    /// their bounding box.
+    pub fn from_universe(universe: &Universe) -> Pattern {
+        let mut cells = Vec::new();
+        let (mut width, mut height) = (0, 0);
+        if let Some((left, top, w, h)) = universe.bounding_box() {
+            universe.cells_in(left, top, w, h, |x, y, state| {
+                cells.push((x - left, y - top, state))
+            });
+            width = w;
+            height = h;
+        }
+        cells.sort_unstable_by_key(|&(x, y, _)| (y, x));
+        Pattern {
+            width,
+            height,
+            cells,
+            rule: Some(universe.rule().to_string()),
+        }
+    }
+
+    /// Sets the cells of the pattern in a universe, with its top left corner
+    /// at `left`, `top`. Fails, setting nothing, if the universe's rule has
+    /// fewer states than the pattern uses.
+    pub fn place(&self, universe: &mut Universe, left: i64, top: i64) -> Result<(), String> {
+        let states = universe.rule().states();
+        if let Some(&(_, _, state)) = self.cells.iter().find(|&&(_, _, state)| state.0 >= states) {
+            return Err(format!(
+                "the pattern has cells in state {}, but {} has {} states",
+                state.0,
+                universe.rule(),
+                states
+            ));
+        }
+        for &(x, y, state) in &self.cells {
+            universe.set(left + x, top + y, state);
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let first = s.lines().map(str::trim).find(|line| !line.is_empty());
+        match first {
+            Some(line) if line.starts_with('#') || line.starts_with('x') => parse_rle(s),
+            _ => parse_plaintext(s),
+        }
+    }
+}
+
+fn parse_rle(s: &str) -> Result<Pattern, String> {
+    let mut lines = s
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+
+    let mut size = None;
+    let mut rule = None;
+    if let Some(header) = lines.next_if(|line| line.starts_with('x')) {
+        // the rule comes last and can have commas of its own, as in
+        // `B3/S23:T80,40`
+        let (fields, rulestring) = match header.find("rule") {
+            Some(i) => (
+                &header[..i],
+                header[i..].split_once('=').map(|(_, rule)| rule),
+            ),
+            None => (header, None),
+        };
+        rule = rulestring.map(|rule| rule.trim().to_string());
+        let (mut width, mut height) = (None, None);
+        for field in fields.split(',').filter(|field| !field.trim().is_empty()) {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or(format!("invalid RLE header '{}'", header))?;
+            let number = || {
+                value
+                    .trim()
+                    .parse::<i64>()
+                    .map_err(|_| format!("invalid RLE header '{}'", header))
+            };
+            match key.trim() {
+                "x" => width = Some(number()?),
+                "y" => height = Some(number()?),
+                _ => {}
+            }
+        }
+        if let (Some(width), Some(height)) = (width, height) {
+            size = Some((width, height));
+        }
+    }
+
+    let mut cells = Vec::new();
+    let (mut x, mut y) = (0, 0);
+    let mut count: Option<i64> = None;
+    // the `p` to `y` before a state above 24
+    let mut prefix = None;
+    'body: for line in lines {
+        for c in line.chars() {
+            let run = count.unwrap_or(1);
+            let state = match c {
+                '0'..='9' => {
+                    let digit = c.to_digit(10).unwrap() as i64;
+                    let run = count.unwrap_or(0) * 10 + digit;
+                    if run > MAX_RUN {
+                        return Err(format!("RLE run longer than {} cells", MAX_RUN));
+                    }
+                    count = Some(run);
+                    continue;
+                }
+                _ if prefix.is_some() && !('A'..='X').contains(&c) => {
+                    return Err(format!("unexpected '{}' after a state prefix in RLE", c));
+                }
+                'p'..='y' => {
+                    prefix = Some(c as u32 - 'p' as u32 + 1);
+                    continue;
+                }
+                'b' | '.' => 0,
+                'o' => 1,
+                'A'..='X' => {
+                    let state = prefix.take().unwrap_or(0) * 24 + (c as u32 - 'A' as u32 + 1);
+                    if state > u8::MAX as u32 {
+                        return Err(format!("RLE state {} is above {}", state, u8::MAX));
+                    }
+                    state as u8
+                }
+                '$' => {
+                    x = 0;
+                    y += run;
+                    count = None;
+                    continue;
+                }
+                '!' => break 'body,
+                c if c.is_whitespace() => continue,
+                c => return Err(format!("unexpected '{}' in RLE", c)),
+            };
+            if state != 0 {
+                cells.extend((x..x + run).map(|x| (x, y, CellState(state))));
+            }
+            x += run;
+            count = None;
+        }
+    }
+
+    Ok(sized(cells, size, rule))
+}
+
+fn parse_plaintext(s: &str) -> Result<Pattern, String> {
+    let mut cells = Vec::new();
+    let rows = s.lines().filter(|line| !line.starts_with('!'));
+    for (y, line) in rows.enumerate() {
+        for (x, c) in line.trim_end().chars().enumerate() {
+            match c {
+                '.' => {}
+                'O' | 'o' | '*' => cells.push((x as i64, y as i64, CellState::ALIVE)),
+                c => return Err(format!("unexpected '{}' in plaintext pattern", c)),
+            }
+        }
+    }
+    Ok(sized(cells, None, None))
+}
+
+// a pattern of the given size, or just big enough for its cells
+fn sized(
+    cells: Vec<(i64, i64, CellState)>,
+    size: Option<(i64, i64)>,
+    rule: Option<String>,
+) -> Pattern {
+    let (width, height) = size.unwrap_or_else(|| {
+        cells.iter().fold((0, 0), |(width, height), &(x, y, _)| {
+            (width.max(x + 1), height.max(y + 1))
+        })
+    });
+    Pattern {
+        width,
+        height,
+        cells,
+        rule,
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "x = {}, y = {}", self.width, self.height)?;
+        if let Some(rule) = &self.rule {
+            write!(f, ", rule = {}", rule)?;
+        }
+        writeln!(f)?;
+
+        let multistate = self.cells.iter().any(|&(_, _, state)| state.0 > 1);
+        let mut cells = self.cells.clone();
+        cells.sort_unstable_by_key(|&(x, y, _)| (y, x));
+
+        let mut runs = Vec::new();
+        let (mut x, mut y) = (0, 0);
+        for (cell_x, cell_y, state) in cells {
+            if cell_y > y {
+                runs.push(Run::ROWS(cell_y - y));
+                x = 0;
+                y = cell_y;
+            }
+            if cell_x > x {
+                runs.push(Run::CELLS(cell_x - x, CellState::DEAD));
+            }
+            match runs.last_mut() {
+                Some(Run::CELLS(length, last)) if *last == state => *length += 1,
+                _ => runs.push(Run::CELLS(1, state)),
+            }
+            x = cell_x + 1;
+        }
+
+        let mut line = String::new();
+        for run in runs {
+            let (count, tag) = match run {
+                Run::ROWS(count) => (count, "$".to_string()),
+                Run::CELLS(count, state) => (count, tag(state, multistate)),
+            };
+            let item = if count > 1 {
+                format!("{}{}", count, tag)
+            } else {
+                tag
+            };
+            if line.len() + item.len() > RLE_LINE_LENGTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            line.push_str(&item);
+        }
+        if line.len() + 1 > RLE_LINE_LENGTH {
+            writeln!(f, "{}", line)?;
+            line.clear();
+        }
+        writeln!(f, "{}!", line)
+    }
+}
+
+enum Run {
+    // ends this many rows
+    ROWS(i64),
+    // this many cells in a state
+    CELLS(i64, CellState),
+}
+
+// the RLE letters for a state
+fn tag(state: CellState, multistate: bool) -> String {
+    let state = state.0;
+    match (state, multistate) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (1..=24, true) => ((b'A' + state - 1) as char).to_string(),
+        (_, true) => {
+            let (prefix, letter) = ((state - 25) / 24, (state - 25) % 24);
+            format!("{}{}", (b'p' + prefix) as char, (b'A' + letter) as char)
+        }
+    }
+}
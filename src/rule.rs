@@ -0,0 +1,896 @@
+use crate::neighborhood::{Neighborhood, Shape, NEIGHBORS};
+use crate::topology::Topology;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// State of a single cell. Rules with more than two states (Generations)
+/// use `2..states` for cells that are dying.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct CellState(pub u8);
+
+impl CellState {
+    pub const DEAD: CellState = CellState(0);
+    pub const ALIVE: CellState = CellState(1);
+
+    // WireWorld: electron heads are what neighbors count, like live cells
+    pub const HEAD: CellState = CellState(1);
+    pub const TAIL: CellState = CellState(2);
+    pub const CONDUCTOR: CellState = CellState(3);
+}
+
+/// A cell that changed during a generation, and the state it changed to.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Change {
+    pub x: i64,
+    pub y: i64,
+    pub state: CellState,
+}
+
+/// A Life-like rule such as `B3/S23`, `B2/S34H` or the isotropic
+/// non-totalistic `B2-a/S12`, or a Larger than Life rule such as
+/// `R5,C0,M1,S34..58,B34..45,NM`, optionally with extra dying states as in
+/// the Generations family (`B2/S/C3`). `WireWorld` is built in as well.
+///
+/// Birth and survival are tables indexed by whatever the neighborhood hands
+/// the rule: the configuration of the neighbors (so totalistic rules
+/// are just the case where every configuration with the same neighbor count
+/// agrees), or the number of live cells in range.
+///
+/// Any rule may end in a bounded grid suffix such as `:T80,40`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    neighborhood: Neighborhood,
+    birth: Vec<bool>,
+    survival: Vec<bool>,
+    // whether a live cell counts itself (Larger than Life's `M1`)
+    middle: bool,
+    states: u8,
+    wireworld: bool,
+    topology: Option<Topology>,
+}
+
+impl Rule {
+    pub fn wireworld() -> Rule {
+        Rule {
+            neighborhood: Neighborhood::MOORE,
+            birth: Vec::new(),
+            survival: Vec::new(),
+            middle: false,
+            states: 4,
+            wireworld: true,
+            topology: None,
+        }
+    }
+
+    pub fn is_wireworld(&self) -> bool {
+        self.wireworld
+    }
+
+    pub fn neighborhood(&self) -> Neighborhood {
+        self.neighborhood
+    }
+
+    pub fn states(&self) -> u8 {
+        self.states
+    }
+
+    /// The bounded grid given by the rulestring, if any.
+    pub fn topology(&self) -> Option<Topology> {
+        self.topology
+    }
+
+    /// Birth and survival by number of live neighbors, for two-state rules
+    /// with a range-1 neighborhood that only depend on that number.
+    pub fn totalistic(&self) -> Option<(Vec<bool>, Vec<bool>)> {
+        if self.wireworld
+            || self.states != 2
+            || matches!(self.neighborhood, Neighborhood::RANGE(..))
+        {
+            return None;
+        }
+
+        // the configuration with the lowest n bits set stands for count n
+        let size = self.neighborhood.offsets(0).len();
+        let birth: Vec<bool> = (0..=size).map(|n| self.birth[(1 << n) - 1]).collect();
+        let survival: Vec<bool> = (0..=size).map(|n| self.survival[(1 << n) - 1]).collect();
+        let agrees = (0..1usize << size).all(|configuration| {
+            let n = configuration.count_ones() as usize;
+            self.birth[configuration] == birth[n] && self.survival[configuration] == survival[n]
+        });
+        if agrees {
+            Some((birth, survival))
+        } else {
+            None
+        }
+    }
+
+    /// `neighbors` is the configuration of live neighbors (see
+    /// `Neighborhood::offsets`) or their count, depending on `neighborhood()`.
+    pub fn next_state(&self, state: CellState, neighbors: usize) -> CellState {
+        if self.wireworld {
+            return match state {
+                CellState::HEAD => CellState::TAIL,
+                CellState::TAIL => CellState::CONDUCTOR,
+                CellState::CONDUCTOR if matches!(neighbors.count_ones(), 1 | 2) => CellState::HEAD,
+                state => state,
+            };
+        }
+
+        let neighbors = if self.middle && state == CellState::ALIVE {
+            neighbors + 1
+        } else {
+            neighbors
+        };
+        if state == CellState::DEAD {
+            if self.birth[neighbors] {
+                CellState::ALIVE
+            } else {
+                CellState::DEAD
+            }
+        } else if state == CellState::ALIVE && self.survival[neighbors] {
+            CellState::ALIVE
+        } else if state.0 + 1 < self.states {
+            // alive cells that don't survive start dying, dying cells keep
+            // counting up until they run out of states
+            CellState(state.0 + 1)
+        } else {
+            CellState::DEAD
+        }
+    }
+
+    /// The state a clicked cell changes to.
+    pub fn edit(&self, state: CellState) -> CellState {
+        if self.wireworld {
+            // lay down wire first, then electrons on it
+            match state {
+                CellState::DEAD => CellState::CONDUCTOR,
+                CellState::CONDUCTOR => CellState::HEAD,
+                CellState::HEAD => CellState::TAIL,
+                _ => CellState::DEAD,
+            }
+        } else if state == CellState::DEAD {
+            CellState::ALIVE
+        } else {
+            CellState::DEAD
+        }
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        "B3/S23".parse().unwrap()
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_transitions(f)?;
+        match self.topology {
+            Some(topology) => write!(f, ":{}", topology),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Rule {
+    fn fmt_transitions(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.wireworld {
+            return write!(f, "WireWorld");
+        }
+        if let Neighborhood::RANGE(shape, range) = self.neighborhood {
+            let (s_min, s_max) = count_range(&self.survival);
+            let (b_min, b_max) = count_range(&self.birth);
+            return write!(
+                f,
+                "R{},C{},M{},S{}..{},B{}..{},N{}",
+                range,
+                if self.states > 2 { self.states } else { 0 },
+                self.middle as u8,
+                s_min,
+                s_max,
+                b_min,
+                b_max,
+                match shape {
+                    Shape::MOORE => 'M',
+                    Shape::VONNEUMANN => 'N',
+                    Shape::CIRCULAR => 'C',
+                }
+            );
+        }
+
+        write!(f, "B")?;
+        write_conditions(f, &self.birth)?;
+        write!(f, "/S")?;
+        write_conditions(f, &self.survival)?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        match self.neighborhood {
+            Neighborhood::VONNEUMANN => write!(f, "V"),
+            Neighborhood::HEXAGONAL => write!(f, "H"),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseRuleError {
+    MissingSlash,
+    TooManyParts,
+    DuplicateSection(char),
+    InvalidDigit(char, usize),
+    InvalidLetter(u32, char),
+    LettersNotAllowed,
+    ConflictingNeighborhood,
+    UnexpectedChar(char),
+    InvalidStates(String),
+    InvalidRange(String),
+    CountTooLarge(usize),
+    MissingField(char),
+    InvalidTopology(String),
+}
+
+impl fmt::Display for ParseRuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseRuleError::MissingSlash => {
+                write!(f, "expected birth and survival separated by '/'")
+            }
+            ParseRuleError::TooManyParts => write!(f, "too many '/'-separated parts"),
+            ParseRuleError::DuplicateSection(c) => write!(f, "'{}' section given twice", c),
+            ParseRuleError::InvalidDigit(c, max) => {
+                write!(f, "neighbor count '{}' is out of range 0-{}", c, max)
+            }
+            ParseRuleError::InvalidLetter(n, c) => {
+                write!(f, "'{}' is not a Hensel letter for {} neighbors", c, n)
+            }
+            ParseRuleError::LettersNotAllowed => {
+                write!(f, "Hensel letters need the Moore neighborhood")
+            }
+            ParseRuleError::ConflictingNeighborhood => {
+                write!(f, "rule already has a different neighborhood")
+            }
+            ParseRuleError::UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            ParseRuleError::InvalidStates(s) => {
+                write!(f, "state count '{}' is not a number from 2 to 255", s)
+            }
+            ParseRuleError::InvalidRange(s) => write!(f, "invalid range '{}'", s),
+            ParseRuleError::CountTooLarge(n) => {
+                write!(f, "neighborhood only has {} cells", n)
+            }
+            ParseRuleError::MissingField(c) => write!(f, "missing '{}' field", c),
+            ParseRuleError::InvalidTopology(s) => write!(f, "invalid bounded grid: {}", s),
+        }
+    }
+}
+
+impl Error for ParseRuleError {}
+
+impl FromStr for Rule {
+    type Err = ParseRuleError;
+
+    /// Parses `B3/S23` notation (in either order, any case) as well as the
+    /// older `S/B` form where `23/3` means survival on 2 or 3, birth on 3.
+    /// Each neighbor count may be followed by Hensel letters selecting
+    /// neighborhood shapes, or by `-` and the letters to exclude, e.g.
+    /// `B2-a/S12` or `B3/S2-i34q`. An optional third part gives the number
+    /// of states for Generations rules, e.g. `B2/S/C3` or `345/2/4`. A `V`
+    /// or `H` suffix selects the von Neumann or hexagonal neighborhood.
+    ///
+    /// Rules starting with `R` are Larger than Life rules, and `WireWorld`
+    /// selects WireWorld. A suffix like `:T80,40` (torus), `:P80,40`
+    /// (plane), `:K80*,40` (Klein bottle), `:C80,40` (cross-surface) or
+    /// `:S80` (sphere) gives the rule a bounded grid.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rule::parse_with_neighborhood(s, None)
+    }
+}
+
+impl Rule {
+    /// Parses a rule like `from_str`, but with the neighborhood given
+    /// separately instead of as a rulestring suffix.
+    pub fn parse_with_neighborhood(
+        s: &str,
+        neighborhood: Option<Neighborhood>,
+    ) -> Result<Rule, ParseRuleError> {
+        let (s, topology) = match s.find(':') {
+            Some(i) => {
+                let topology = s[i + 1..]
+                    .trim()
+                    .parse()
+                    .map_err(ParseRuleError::InvalidTopology)?;
+                (&s[..i], Some(topology))
+            }
+            None => (s, None),
+        };
+        let mut rule = parse_transitions(s, neighborhood)?;
+        rule.topology = topology;
+        Ok(rule)
+    }
+}
+
+fn parse_transitions(s: &str, neighborhood: Option<Neighborhood>) -> Result<Rule, ParseRuleError> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("wireworld") {
+        return match neighborhood {
+            None | Some(Neighborhood::MOORE) => Ok(Rule::wireworld()),
+            Some(_) => Err(ParseRuleError::ConflictingNeighborhood),
+        };
+    }
+    if s.starts_with('R') || s.starts_with('r') {
+        return match neighborhood {
+            Some(_) => Err(ParseRuleError::ConflictingNeighborhood),
+            None => parse_larger_than_life(s),
+        };
+    }
+
+    let (s, suffix) = match s.chars().last() {
+        Some('V') | Some('v') => (&s[..s.len() - 1], Some(Neighborhood::VONNEUMANN)),
+        Some('H') | Some('h') => (&s[..s.len() - 1], Some(Neighborhood::HEXAGONAL)),
+        _ => (s, None),
+    };
+    let neighborhood = match (suffix, neighborhood) {
+        (Some(a), Some(b)) if a != b => return Err(ParseRuleError::ConflictingNeighborhood),
+        (a, b) => a.or(b).unwrap_or(Neighborhood::MOORE),
+    };
+
+    let mut parts: Vec<&str> = s.split('/').collect();
+    if parts.len() < 2 {
+        return Err(ParseRuleError::MissingSlash);
+    }
+    if parts.len() > 3 {
+        return Err(ParseRuleError::TooManyParts);
+    }
+
+    let states = if parts.len() == 3 {
+        parse_states(parts.pop().unwrap())?
+    } else {
+        2
+    };
+
+    let mut birth = None;
+    let mut survival = None;
+    for (i, part) in parts.iter().enumerate() {
+        let (section, digits) = match part.chars().next() {
+            Some(c @ 'B') | Some(c @ 'b') | Some(c @ 'S') | Some(c @ 's') => {
+                (c.to_ascii_uppercase(), &part[1..])
+            }
+            // S/B notation without letters
+            _ if i == 0 || birth.is_some() => ('S', &part[..]),
+            _ => ('B', &part[..]),
+        };
+
+        let slot = if section == 'B' {
+            &mut birth
+        } else {
+            &mut survival
+        };
+        if slot.is_some() {
+            return Err(ParseRuleError::DuplicateSection(section));
+        }
+        *slot = Some(parse_conditions(digits, neighborhood)?);
+    }
+
+    Ok(Rule {
+        neighborhood,
+        birth: birth.unwrap(),
+        survival: survival.unwrap(),
+        middle: false,
+        states,
+        wireworld: false,
+        topology: None,
+    })
+}
+
+fn parse_larger_than_life(s: &str) -> Result<Rule, ParseRuleError> {
+    let mut range = None;
+    let mut states = 2;
+    let mut middle = false;
+    let mut survival = None;
+    let mut birth = None;
+    let mut shape = Shape::MOORE;
+
+    for field in s.split(',') {
+        let mut chars = field.chars();
+        let key = chars.next().map(|c| c.to_ascii_uppercase());
+        let value = chars.as_str();
+        match key {
+            Some('R') => match value.parse::<usize>() {
+                Ok(r) if (1..=500).contains(&r) => range = Some(r),
+                _ => return Err(ParseRuleError::InvalidRange(field.to_string())),
+            },
+            Some('C') => {
+                states = match value.parse::<u8>() {
+                    Ok(0) | Ok(1) => 2,
+                    Ok(c) => c,
+                    Err(_) => return Err(ParseRuleError::InvalidStates(field.to_string())),
+                }
+            }
+            Some('M') => {
+                middle = match value {
+                    "0" => false,
+                    "1" => true,
+                    _ => return Err(ParseRuleError::UnexpectedChar('M')),
+                }
+            }
+            Some('S') => survival = Some(parse_count_range(field, value)?),
+            Some('B') => birth = Some(parse_count_range(field, value)?),
+            Some('N') => {
+                shape = match value {
+                    "M" | "m" => Shape::MOORE,
+                    "N" | "n" => Shape::VONNEUMANN,
+                    "C" | "c" => Shape::CIRCULAR,
+                    _ => return Err(ParseRuleError::UnexpectedChar('N')),
+                }
+            }
+            Some(c) => return Err(ParseRuleError::UnexpectedChar(c)),
+            None => return Err(ParseRuleError::UnexpectedChar(',')),
+        }
+    }
+
+    let range = range.ok_or(ParseRuleError::MissingField('R'))?;
+    let (s_min, s_max) = survival.ok_or(ParseRuleError::MissingField('S'))?;
+    let (b_min, b_max) = birth.ok_or(ParseRuleError::MissingField('B'))?;
+    let size = shape.size(range);
+    if s_min > size || b_min > size {
+        return Err(ParseRuleError::CountTooLarge(size));
+    }
+    Ok(Rule {
+        neighborhood: Neighborhood::RANGE(shape, range),
+        birth: (0..=size).map(|n| (b_min..=b_max).contains(&n)).collect(),
+        survival: (0..=size).map(|n| (s_min..=s_max).contains(&n)).collect(),
+        middle,
+        states,
+        wireworld: false,
+        topology: None,
+    })
+}
+
+fn parse_count_range(field: &str, value: &str) -> Result<(usize, usize), ParseRuleError> {
+    let bounds = match value.find("..") {
+        Some(i) => (value[..i].parse(), value[i + 2..].parse()),
+        None => (value.parse(), value.parse()),
+    };
+    match bounds {
+        (Ok(min), Ok(max)) if min <= max => Ok((min, max)),
+        _ => Err(ParseRuleError::InvalidRange(field.to_string())),
+    }
+}
+
+fn count_range(table: &[bool]) -> (usize, usize) {
+    let min = table.iter().position(|&t| t).unwrap_or(table.len());
+    let max = table.iter().rposition(|&t| t).unwrap_or(0);
+    (min, max)
+}
+
+fn parse_states(part: &str) -> Result<u8, ParseRuleError> {
+    let digits = part
+        .strip_prefix('C')
+        .or_else(|| part.strip_prefix('c'))
+        .unwrap_or(part);
+    match digits.parse::<u8>() {
+        Ok(states) if states >= 2 => Ok(states),
+        _ => Err(ParseRuleError::InvalidStates(part.to_string())),
+    }
+}
+
+fn parse_conditions(digits: &str, neighborhood: Neighborhood) -> Result<Vec<bool>, ParseRuleError> {
+    let max = neighborhood.offsets(0).len();
+    let mut table = vec![false; 256];
+    let mut chars = digits.chars().peekable();
+    while let Some(c) = chars.next() {
+        let n = match c.to_digit(10) {
+            Some(n) if n as usize <= max => n,
+            Some(_) => return Err(ParseRuleError::InvalidDigit(c, max)),
+            None => return Err(ParseRuleError::UnexpectedChar(c)),
+        };
+
+        let negate = chars.peek() == Some(&'-');
+        if negate {
+            chars.next();
+        }
+        let mut letters = Vec::new();
+        while let Some(&l) = chars.peek().filter(|l| l.is_ascii_lowercase()) {
+            if neighborhood != Neighborhood::MOORE {
+                return Err(ParseRuleError::LettersNotAllowed);
+            }
+            if !hensel_letters(n).contains(l) {
+                return Err(ParseRuleError::InvalidLetter(n, l));
+            }
+            letters.push(l);
+            chars.next();
+        }
+        if negate && letters.is_empty() {
+            return Err(ParseRuleError::UnexpectedChar('-'));
+        }
+
+        if letters.is_empty() {
+            for (config, entry) in table.iter_mut().enumerate() {
+                if config.count_ones() == n {
+                    *entry = true;
+                }
+            }
+        } else {
+            for l in hensel_letters(n).chars() {
+                if letters.contains(&l) != negate {
+                    for config in symmetries(hensel_configuration(n, l)) {
+                        table[config as usize] = true;
+                    }
+                }
+            }
+        }
+    }
+    Ok(table)
+}
+
+fn write_conditions(f: &mut fmt::Formatter, table: &[bool]) -> fmt::Result {
+    for n in 0..9 {
+        let letters = hensel_letters(n);
+        if letters.is_empty() {
+            if table
+                .iter()
+                .enumerate()
+                .any(|(c, &t)| t && c.count_ones() == n)
+            {
+                write!(f, "{}", n)?;
+            }
+            continue;
+        }
+
+        let (with, without): (String, String) = letters
+            .chars()
+            .partition(|&l| table[hensel_configuration(n, l) as usize]);
+        if without.is_empty() {
+            write!(f, "{}", n)?;
+        } else if with.len() > without.len() {
+            write!(f, "{}-{}", n, without)?;
+        } else if !with.is_empty() {
+            write!(f, "{}{}", n, with)?;
+        }
+    }
+    Ok(())
+}
+
+// Hensel letters for 1 to 4 neighbors. 5 to 7 neighbors reuse the letters of
+// 8 - n with the configuration complemented.
+const HENSEL_LETTERS: [&str; 5] = ["", "ce", "ceaikn", "ceaiknjqry", "ceaiknjqrytwz"];
+
+// One configuration for each entry of `HENSEL_LETTERS`, as bits of `NEIGHBORS`.
+const HENSEL_CONFIGURATIONS: [&[u8]; 5] = [
+    &[],
+    &[0x01, 0x02],
+    &[0x05, 0x0a, 0x03, 0x18, 0x11, 0x24],
+    &[0x25, 0x1a, 0x0b, 0x07, 0x32, 0x0d, 0x0e, 0x26, 0x19, 0x31],
+    &[
+        0xa5, 0x5a, 0x0f, 0x1d, 0x33, 0x27, 0x3a, 0x36, 0x1b, 0x35, 0x39, 0x2e, 0x3c,
+    ],
+];
+
+fn hensel_index(n: u32) -> usize {
+    4 - (n as i32 - 4).unsigned_abs() as usize
+}
+
+fn hensel_letters(n: u32) -> &'static str {
+    HENSEL_LETTERS[hensel_index(n)]
+}
+
+fn hensel_configuration(n: u32, letter: char) -> u8 {
+    let m = hensel_index(n);
+    let i = HENSEL_LETTERS[m].find(letter).unwrap();
+    let config = HENSEL_CONFIGURATIONS[m][i];
+    if n > 4 {
+        !config
+    } else {
+        config
+    }
+}
+
+type Transform = fn(isize, isize) -> (isize, isize);
+
+// All rotations and reflections of a neighborhood configuration.
+fn symmetries(config: u8) -> Vec<u8> {
+    let transforms: [Transform; 8] = [
+        |x, y| (x, y),
+        |x, y| (-y, x),
+        |x, y| (-x, -y),
+        |x, y| (y, -x),
+        |x, y| (-x, y),
+        |x, y| (x, -y),
+        |x, y| (y, x),
+        |x, y| (-y, -x),
+    ];
+    transforms
+        .iter()
+        .map(|transform| {
+            let mut result = 0;
+            for (bit, &(dx, dy)) in NEIGHBORS.iter().enumerate() {
+                if config & (1 << bit) != 0 {
+                    let moved = transform(dx, dy);
+                    result |= 1 << NEIGHBORS.iter().position(|&n| n == moved).unwrap();
+                }
+            }
+            result
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::universe::{Algorithm, Universe};
+
+    const NW: (isize, isize) = (-1, -1);
+    const N: (isize, isize) = (0, -1);
+    const NE: (isize, isize) = (1, -1);
+    const W: (isize, isize) = (-1, 0);
+    const E: (isize, isize) = (1, 0);
+    const SW: (isize, isize) = (-1, 1);
+    const S: (isize, isize) = (0, 1);
+    const SE: (isize, isize) = (1, 1);
+
+    // the configuration with live neighbors at the given offsets
+    fn configuration(neighbors: &[(isize, isize)]) -> usize {
+        neighbors
+            .iter()
+            .map(|neighbor| 1 << NEIGHBORS.iter().position(|n| n == neighbor).unwrap())
+            .sum()
+    }
+
+    fn born(rulestring: &str, neighbors: &[(isize, isize)]) -> bool {
+        let rule: Rule = rulestring.parse().unwrap();
+        rule.next_state(CellState::DEAD, configuration(neighbors)) == CellState::ALIVE
+    }
+
+    // the shapes drawn for each letter on LifeWiki, in one of their
+    // orientations
+    const SHAPES: [(&str, &[(isize, isize)]); 14] = [
+        ("1c", &[NW]),
+        ("1e", &[N]),
+        ("2a", &[NW, N]),
+        ("2c", &[NW, NE]),
+        ("2e", &[N, W]),
+        ("2i", &[N, S]),
+        ("2k", &[NW, E]),
+        ("2n", &[NE, SW]),
+        ("3c", &[NW, NE, SE]),
+        ("3e", &[N, W, S]),
+        ("3a", &[NW, N, W]),
+        ("3i", &[NW, N, NE]),
+        ("4c", &[NW, NE, SW, SE]),
+        ("4e", &[N, W, E, S]),
+    ];
+
+    #[test]
+    fn hensel_letters_match_their_shapes() {
+        for (name, shape) in &SHAPES {
+            // turned a quarter at a time and mirrored
+            let mut turned: Vec<(isize, isize)> = shape.to_vec();
+            for turn in 0..8 {
+                if turn == 4 {
+                    turned = turned.iter().map(|&(x, y)| (-x, y)).collect();
+                }
+                for (other, _) in SHAPES.iter().filter(|(other, _)| other[..1] == name[..1]) {
+                    let rulestring = format!("B{}/S", other);
+                    assert_eq!(
+                        born(&rulestring, &turned),
+                        other == name,
+                        "{} with the neighbors of {} at {:?}",
+                        rulestring,
+                        name,
+                        turned
+                    );
+                }
+                turned = turned.iter().map(|&(x, y)| (-y, x)).collect();
+            }
+        }
+    }
+
+    #[test]
+    fn letters_above_four_are_complements() {
+        // 7c is missing a corner, 7e an edge, 6i the two opposite edges
+        assert!(born("B7c/S", &[N, NE, W, E, SW, S, SE]));
+        assert!(!born("B7c/S", &[NW, NE, W, E, SW, S, SE]));
+        assert!(born("B7e/S", &[NW, NE, W, E, SW, S, SE]));
+        assert!(born("B6i/S", &[NW, NE, W, E, SW, SE]));
+        assert!(!born("B6i/S", &[NW, N, NE, E, SW, SE]));
+    }
+
+    #[test]
+    fn letters_split_each_count() {
+        // every configuration with n live neighbors has exactly one letter
+        for n in 1..8 {
+            let tables: Vec<Vec<bool>> = hensel_letters(n)
+                .chars()
+                .map(|letter| parse_conditions(&format!("{}{}", n, letter), Neighborhood::MOORE))
+                .collect::<Result<_, _>>()
+                .unwrap();
+            for config in 0..256 {
+                let letters = tables.iter().filter(|table| table[config]).count();
+                let expected = (config.count_ones() == n) as usize;
+                assert_eq!(
+                    letters, expected,
+                    "{} neighbors, configuration {:#x}",
+                    n, config
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tlife_steps_a_row_of_three() {
+        // in Life the row of three is a blinker; in tlife the middle cell has
+        // its two neighbors on opposite sides (2i) and dies
+        let rule: Rule = "B3/S2-i34q".parse().unwrap();
+        let mut universe = Universe::new(rule, Algorithm::SPARSE, (0, 0)).unwrap();
+        for x in 0..3 {
+            universe.set(x, 0, CellState::ALIVE);
+        }
+        universe.tick();
+        let mut cells = Vec::new();
+        universe.cells_in(-5, -5, 10, 10, |x, y, _| cells.push((x, y)));
+        cells.sort_unstable();
+        assert_eq!(cells, vec![(1, -1), (1, 1)]);
+
+        // four neighbors other than 4q, such as the corners, kill a cell
+        let rule: Rule = "B3/S2-i34q".parse().unwrap();
+        let corners = rule.next_state(CellState::ALIVE, configuration(&[NW, NE, SW, SE]));
+        let edges = rule.next_state(CellState::ALIVE, configuration(&[N, W, E, S]));
+        assert_eq!((corners, edges), (CellState::DEAD, CellState::DEAD));
+    }
+
+    fn error(rulestring: &str) -> ParseRuleError {
+        rulestring.parse::<Rule>().unwrap_err()
+    }
+
+    #[test]
+    fn parses_birth_and_survival() {
+        let life: Rule = "B3/S23".parse().unwrap();
+        assert_eq!(
+            life.totalistic(),
+            Some((counts(&[3], 8), counts(&[2, 3], 8)))
+        );
+        assert_eq!(life.neighborhood(), Neighborhood::MOORE);
+        assert_eq!(life.states(), 2);
+        // either order, any case, and the older S/B form
+        for rulestring in &["S23/B3", "b3/s23", "23/3"] {
+            assert_eq!(rulestring.parse::<Rule>().unwrap(), life, "{}", rulestring);
+        }
+        assert_eq!(Rule::default(), life);
+    }
+
+    // a table that is true for the given counts
+    fn counts(counts: &[usize], size: usize) -> Vec<bool> {
+        (0..=size).map(|n| counts.contains(&n)).collect()
+    }
+
+    #[test]
+    fn displays_what_it_parses() {
+        let rulestrings = [
+            "B3/S23",
+            "B/S",
+            "B36/S23",
+            "B2-a/S12",
+            "B3/S2-i34q",
+            "B2/S/C3",
+            "B2/S345/C4",
+            "B2/S013V",
+            "B2/S34H",
+            "R5,C0,M1,S34..58,B34..45,NM",
+            "R2,C3,M0,S2..5,B3..4,NC",
+            "R3,C0,M1,S1..4,B2..3,NN",
+            "WireWorld",
+            "B3/S23:T80,40",
+            "B2/S/C3:K20*,10",
+        ];
+        for rulestring in &rulestrings {
+            let rule: Rule = rulestring.parse().unwrap();
+            assert_eq!(rule.to_string(), *rulestring);
+        }
+        // written the way it is usually written
+        let rule: Rule = "345/2/4".parse().unwrap();
+        assert_eq!(rule.to_string(), "B2/S345/C4");
+        let rule: Rule = "R5,C0,M1,S34..58,B34..45".parse().unwrap();
+        assert_eq!(rule.to_string(), "R5,C0,M1,S34..58,B34..45,NM");
+    }
+
+    #[test]
+    fn parses_other_families() {
+        let generations: Rule = "B2/S/C3".parse().unwrap();
+        assert_eq!(generations.states(), 3);
+        assert_eq!(generations.next_state(CellState::ALIVE, 0), CellState(2));
+        assert_eq!(generations.next_state(CellState(2), 0b11), CellState::DEAD);
+
+        let von_neumann: Rule = "B2/S013V".parse().unwrap();
+        assert_eq!(von_neumann.neighborhood(), Neighborhood::VONNEUMANN);
+        assert_eq!(
+            von_neumann.totalistic(),
+            Some((counts(&[2], 4), counts(&[0, 1, 3], 4)))
+        );
+        let hexagonal: Rule = "B2/S34H".parse().unwrap();
+        assert_eq!(hexagonal.neighborhood(), Neighborhood::HEXAGONAL);
+        assert_eq!(
+            Rule::parse_with_neighborhood("B2/S34", Some(Neighborhood::HEXAGONAL)).unwrap(),
+            hexagonal
+        );
+
+        let bosco: Rule = "R5,C0,M1,S34..58,B34..45,NM".parse().unwrap();
+        assert_eq!(bosco.neighborhood(), Neighborhood::RANGE(Shape::MOORE, 5));
+        assert_eq!(bosco.totalistic(), None);
+        // the middle cell counts towards survival
+        assert_eq!(bosco.next_state(CellState::ALIVE, 33), CellState::ALIVE);
+        assert_eq!(bosco.next_state(CellState::DEAD, 33), CellState::DEAD);
+        assert_eq!(bosco.next_state(CellState::DEAD, 45), CellState::ALIVE);
+
+        let wireworld: Rule = "wireworld".parse().unwrap();
+        assert!(wireworld.is_wireworld());
+        assert_eq!(wireworld.states(), 4);
+        let conductor = CellState::CONDUCTOR;
+        assert_eq!(wireworld.next_state(conductor, 0b11), CellState::HEAD);
+        assert_eq!(wireworld.next_state(conductor, 0b111), conductor);
+        assert_eq!(wireworld.next_state(CellState::HEAD, 0), CellState::TAIL);
+    }
+
+    #[test]
+    fn reports_each_error() {
+        assert_eq!(error("B3S23"), ParseRuleError::MissingSlash);
+        assert_eq!(error("B3/S23/C3/4"), ParseRuleError::TooManyParts);
+        assert_eq!(error("B3/B23"), ParseRuleError::DuplicateSection('B'));
+        assert_eq!(error("S3/S23"), ParseRuleError::DuplicateSection('S'));
+        assert_eq!(error("B9/S23"), ParseRuleError::InvalidDigit('9', 8));
+        assert_eq!(error("B5/S23V"), ParseRuleError::InvalidDigit('5', 4));
+        assert_eq!(error("B3z/S23"), ParseRuleError::InvalidLetter(3, 'z'));
+        assert_eq!(error("B2a/S23H"), ParseRuleError::LettersNotAllowed);
+        assert_eq!(error("B3!/S23"), ParseRuleError::UnexpectedChar('!'));
+        assert_eq!(error("B3-/S23"), ParseRuleError::UnexpectedChar('-'));
+        assert_eq!(
+            error("B2/S/C1"),
+            ParseRuleError::InvalidStates("C1".to_string())
+        );
+        assert_eq!(
+            error("B2/S/C256"),
+            ParseRuleError::InvalidStates("C256".to_string())
+        );
+        assert_eq!(
+            Rule::parse_with_neighborhood("B2/S34H", Some(Neighborhood::VONNEUMANN)),
+            Err(ParseRuleError::ConflictingNeighborhood)
+        );
+        assert_eq!(
+            Rule::parse_with_neighborhood("WireWorld", Some(Neighborhood::HEXAGONAL)),
+            Err(ParseRuleError::ConflictingNeighborhood)
+        );
+        assert!(matches!(
+            error("B3/S23:X10"),
+            ParseRuleError::InvalidTopology(_)
+        ));
+
+        assert_eq!(
+            error("R0,C0,M1,S2..5,B3..4,NM"),
+            ParseRuleError::InvalidRange("R0".to_string())
+        );
+        assert_eq!(
+            error("R2,C0,M1,S5..2,B3..4,NM"),
+            ParseRuleError::InvalidRange("S5..2".to_string())
+        );
+        assert_eq!(
+            error("R2,C0,M1,S2..5,NM"),
+            ParseRuleError::MissingField('B')
+        );
+        assert_eq!(
+            error("R1,C0,M1,S10..12,B3..4,NM"),
+            ParseRuleError::CountTooLarge(9)
+        );
+        assert_eq!(
+            error("R2,C0,M2,S2..5,B3..4,NM"),
+            ParseRuleError::UnexpectedChar('M')
+        );
+        assert_eq!(
+            error("R2,C0,M1,S2..5,B3..4,NX"),
+            ParseRuleError::UnexpectedChar('N')
+        );
+        assert_eq!(
+            error("R2,C0,M1,S2..5,B3..4,X1"),
+            ParseRuleError::UnexpectedChar('X')
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use crate::engine::{self, Engine};
+use crate::neighborhood::Neighborhood;
+use crate::rule::{CellState, Change, Rule};
+use crate::topology::Topology;
+use std::collections::HashMap;
+
+/// An unbounded universe that only stores the cells that aren't dead, so
+/// patterns can travel arbitrarily far.
+pub struct Sparse {
+    rule: Rule,
+    cells: HashMap<(i64, i64), CellState>,
+    changes: Vec<Change>,
+}
+
+impl Sparse {
+    /// Fails for rules where empty space comes alive (`B0`), which would
+    /// fill the whole plane.
+    pub fn new(rule: Rule) -> Result<Self, String> {
+        if rule.next_state(CellState::DEAD, 0) != CellState::DEAD {
+            return Err(format!("{} needs a bounded universe", rule));
+        }
+        Ok(Sparse {
+            rule,
+            cells: HashMap::new(),
+            changes: Vec::new(),
+        })
+    }
+
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> CellState {
+        self.cells.get(&(x, y)).copied().unwrap_or(CellState::DEAD)
+    }
+
+    pub fn set(&mut self, x: i64, y: i64, state: CellState) {
+        if state == CellState::DEAD {
+            self.cells.remove(&(x, y));
+        } else {
+            self.cells.insert((x, y), state);
+        }
+    }
+
+    /// Every cell that isn't dead, in no particular order.
+    pub fn cells(&self) -> impl Iterator<Item = ((i64, i64), CellState)> + '_ {
+        self.cells
+            .iter()
+            .map(|(&position, &state)| (position, state))
+    }
+
+    /// Advances one generation and returns the cells that changed.
+    pub fn tick(&mut self) -> &[Change] {
+        // Every live cell adds itself to the neighbors of the cells around
+        // it. Cells nobody added to have no live neighbors.
+        let mut neighbors: HashMap<(i64, i64), usize> = HashMap::new();
+        let alive = self
+            .cells
+            .iter()
+            .filter(|&(_, &state)| state == CellState::ALIVE)
+            .map(|(&position, _)| position);
+        match self.rule.neighborhood() {
+            Neighborhood::RANGE(shape, range) => {
+                let offsets = shape.offsets(range);
+                for (x, y) in alive {
+                    for &(dx, dy) in &offsets {
+                        *neighbors.entry((x - dx as i64, y - dy as i64)).or_insert(0) += 1;
+                    }
+                }
+            }
+            neighborhood => {
+                for (x, y) in alive {
+                    // the offsets depend on the row of the cell being
+                    // updated, which is only known once an offset is picked
+                    for parity in 0..2 {
+                        let offsets = neighborhood.offsets(parity);
+                        for (bit, &(dx, dy)) in offsets.iter().enumerate() {
+                            let (nx, ny) = (x - dx as i64, y - dy as i64);
+                            if ny.rem_euclid(2) as isize == parity {
+                                *neighbors.entry((nx, ny)).or_insert(0) |= 1 << bit;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut changes = std::mem::take(&mut self.changes);
+        changes.clear();
+        for (&(x, y), &state) in &self.cells {
+            if !neighbors.contains_key(&(x, y)) {
+                let next = self.rule.next_state(state, 0);
+                if next != state {
+                    changes.push(Change { x, y, state: next });
+                }
+            }
+        }
+        for ((x, y), neighbors) in neighbors {
+            let state = self.get(x, y);
+            let next = self.rule.next_state(state, neighbors);
+            if next != state {
+                changes.push(Change { x, y, state: next });
+            }
+        }
+
+        for change in &changes {
+            self.set(change.x, change.y, change.state);
+        }
+        self.changes = changes;
+        &self.changes
+    }
+}
+
+impl Engine for Sparse {
+    fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    fn topology(&self) -> Option<Topology> {
+        None
+    }
+
+    fn get(&self, x: i64, y: i64) -> CellState {
+        Sparse::get(self, x, y)
+    }
+
+    fn set(&mut self, x: i64, y: i64, state: CellState) {
+        Sparse::set(self, x, y, state);
+    }
+
+    fn cells_in(
+        &self,
+        left: i64,
+        top: i64,
+        width: i64,
+        height: i64,
+        f: &mut dyn FnMut(i64, i64, CellState),
+    ) {
+        for ((x, y), state) in self.cells() {
+            if left <= x && x < left + width && top <= y && y < top + height {
+                f(x, y, state);
+            }
+        }
+    }
+
+    fn tick(&mut self) -> Option<&[Change]> {
+        Some(Sparse::tick(self))
+    }
+
+    fn population(&self) -> u64 {
+        self.cells.len() as u64
+    }
+
+    fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        engine::bounding_box(self.cells.keys().copied())
+    }
+}
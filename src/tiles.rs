@@ -0,0 +1,91 @@
+/// Which tiles of a grid changed in the last generation. A cell can only
+/// change if something near it did, so only those tiles and the ones around
+/// them need stepping, and still or empty areas are skipped (like the
+/// sleeping tiles of Golly's QuickLife).
+///
+/// The grids step each generation from one buffer into another and then swap
+/// them, writing only the active tiles. The others are left as they were, so
+/// a grid has to keep both buffers the same outside them: after swapping, it
+/// copies the cells that changed into the buffer it will step into next.
+pub struct Tiles {
+    columns: usize,
+    rows: usize,
+    changed: Vec<bool>,
+    changed_list: Vec<usize>,
+    // a cell on the edge of the grid changed, which can affect cells on
+    // whichever edge it is joined to
+    edge_changed: bool,
+    active: Vec<bool>,
+}
+
+impl Tiles {
+    /// Starts with every tile changed.
+    pub fn new(columns: usize, rows: usize) -> Self {
+        Tiles {
+            columns,
+            rows,
+            changed: vec![true; columns * rows],
+            changed_list: (0..columns * rows).collect(),
+            edge_changed: true,
+            active: vec![false; columns * rows],
+        }
+    }
+
+    pub fn mark(&mut self, column: usize, row: usize) {
+        let tile = row * self.columns + column;
+        if !self.changed[tile] {
+            self.changed[tile] = true;
+            self.changed_list.push(tile);
+        }
+    }
+
+    pub fn mark_edge(&mut self) {
+        self.edge_changed = true;
+    }
+
+    pub fn edge_changed(&self) -> bool {
+        self.edge_changed
+    }
+
+    /// The tiles to step, as `row * columns + column` in increasing order:
+    /// the changed ones and their neighbors, and all tiles on the edges if
+    /// an edge cell changed. Forgets the changes.
+    pub fn take_active(&mut self) -> Vec<usize> {
+        let (columns, rows) = (self.columns, self.rows);
+        let mut active = Vec::new();
+        let mut add = |tile: usize, flags: &mut Vec<bool>| {
+            if !flags[tile] {
+                flags[tile] = true;
+                active.push(tile);
+            }
+        };
+
+        for &tile in &self.changed_list {
+            self.changed[tile] = false;
+            let (column, row) = (tile % columns, tile / columns);
+            for r in row.saturating_sub(1)..(row + 2).min(rows) {
+                for c in column.saturating_sub(1)..(column + 2).min(columns) {
+                    add(r * columns + c, &mut self.active);
+                }
+            }
+        }
+        if self.edge_changed {
+            for c in 0..columns {
+                add(c, &mut self.active);
+                add((rows - 1) * columns + c, &mut self.active);
+            }
+            for r in 0..rows {
+                add(r * columns, &mut self.active);
+                add(r * columns + columns - 1, &mut self.active);
+            }
+        }
+
+        for &tile in &active {
+            self.active[tile] = false;
+        }
+        self.changed_list.clear();
+        self.edge_changed = false;
+        active.sort_unstable();
+        active
+    }
+}
@@ -0,0 +1,381 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// The size of a bounded universe and how its edges are joined, written as
+/// a Golly-style rulestring suffix such as `:T80,40`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Topology {
+    pub surface: Surface,
+    pub width: usize,
+    pub height: usize,
+    /// On a torus, how many cells further right a cell leaving through the
+    /// bottom edge comes back through the top (`:T80+2,40`), and how many
+    /// further down one leaving through the right edge comes back through
+    /// the left (`:T80,40+2`). At most one of them is not zero.
+    pub shift: (isize, isize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Surface {
+    /// Cells beyond the edges are always dead (`:P`).
+    PLANE,
+    /// Opposite edges are joined (`:T`).
+    TORUS,
+    /// Opposite edges are joined, one pair with a twist (`:K`).
+    KLEIN(Twist),
+    /// Both pairs of opposite edges are joined with a twist (`:C`).
+    CROSSSURFACE,
+    /// The top edge is joined to the left edge and the bottom edge to the
+    /// right edge of a square (`:S`).
+    SPHERE,
+}
+
+/// Which pair of edges of a Klein bottle is joined with a twist.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Twist {
+    /// Leaving through the top or bottom edge mirrors the column (`:K80*,40`).
+    HORIZONTAL,
+    /// Leaving through the left or right edge mirrors the row (`:K80,40*`).
+    VERTICAL,
+}
+
+impl Topology {
+    pub fn torus(width: usize, height: usize) -> Self {
+        Topology {
+            surface: Surface::TORUS,
+            width,
+            height,
+            shift: (0, 0),
+        }
+    }
+
+    /// Maps a position that may lie beyond the edges to the cell it refers
+    /// to, or `None` if that cell is always dead. On the cross-surface and
+    /// the sphere, positions beyond two edges at once (diagonally past a
+    /// corner) are treated as dead.
+    pub fn map(&self, x: isize, y: isize) -> Option<(usize, usize)> {
+        let (w, h) = (self.width as isize, self.height as isize);
+        let inside_x = 0 <= x && x < w;
+        let inside_y = 0 <= y && y < h;
+        if inside_x && inside_y {
+            return Some((x as usize, y as usize));
+        }
+
+        let (x, y) = match self.surface {
+            Surface::PLANE => return None,
+            Surface::TORUS => {
+                let (x_shift, y_shift) = self.shift;
+                let x = x + y.div_euclid(h) * x_shift;
+                let y = y + x.div_euclid(w) * y_shift;
+                (x.rem_euclid(w), y.rem_euclid(h))
+            }
+            Surface::KLEIN(Twist::HORIZONTAL) => {
+                let x = if y.div_euclid(h) % 2 != 0 {
+                    w - 1 - x
+                } else {
+                    x
+                };
+                (x.rem_euclid(w), y.rem_euclid(h))
+            }
+            Surface::KLEIN(Twist::VERTICAL) => {
+                let y = if x.div_euclid(w) % 2 != 0 {
+                    h - 1 - y
+                } else {
+                    y
+                };
+                (x.rem_euclid(w), y.rem_euclid(h))
+            }
+            Surface::CROSSSURFACE => {
+                if !inside_x && !inside_y {
+                    return None;
+                } else if !inside_y {
+                    (w - 1 - x, y.rem_euclid(h))
+                } else {
+                    (x.rem_euclid(w), h - 1 - y)
+                }
+            }
+            Surface::SPHERE => {
+                // w == h, and crossing an edge turns the corner onto the
+                // edge it is joined to
+                if !inside_x && !inside_y {
+                    return None;
+                } else if y < 0 {
+                    (-y - 1, x)
+                } else if x < 0 {
+                    (y, -x - 1)
+                } else if y >= h {
+                    (2 * h - 1 - y, x)
+                } else {
+                    (y, 2 * w - 1 - x)
+                }
+            }
+        };
+
+        if 0 <= x && x < w && 0 <= y && y < h {
+            Some((x as usize, y as usize))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.surface {
+            Surface::PLANE => write!(f, "P{},{}", self.width, self.height),
+            Surface::TORUS => {
+                let shift = |shift: isize| match shift {
+                    0 => String::new(),
+                    _ => format!("{:+}", shift),
+                };
+                write!(
+                    f,
+                    "T{}{},{}{}",
+                    self.width,
+                    shift(self.shift.0),
+                    self.height,
+                    shift(self.shift.1)
+                )
+            }
+            Surface::KLEIN(Twist::HORIZONTAL) => write!(f, "K{}*,{}", self.width, self.height),
+            Surface::KLEIN(Twist::VERTICAL) => write!(f, "K{},{}*", self.width, self.height),
+            Surface::CROSSSURFACE => write!(f, "C{},{}", self.width, self.height),
+            Surface::SPHERE => write!(f, "S{}", self.width),
+        }
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    /// Parses the part of a rulestring after the `:`, e.g. `T80,40`,
+    /// `T80+2,40`, `K80*,40` or `S80`. A single size means a square.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        let kind = chars.next().map(|c| c.to_ascii_uppercase());
+        let sizes: Vec<&str> = chars.as_str().split(',').collect();
+        if sizes.len() > 2 {
+            return Err(format!("too many sizes in '{}'", s));
+        }
+
+        let parse_size = |size: &str| match size.trim_end_matches('*').parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("'{}' is not a positive grid size", size)),
+        };
+        let (width, x_shift) = split_shift(sizes[0])?;
+        let width = parse_size(width)?;
+        let (height, y_shift) = match sizes.get(1) {
+            Some(size) => {
+                let (height, shift) = split_shift(size)?;
+                (parse_size(height)?, shift)
+            }
+            None => (width, 0),
+        };
+        let starred: Vec<bool> = sizes.iter().map(|size| size.ends_with('*')).collect();
+
+        let surface = match kind {
+            Some('P') => Surface::PLANE,
+            Some('T') => Surface::TORUS,
+            Some('K') => match starred.as_slice() {
+                [true, false] | [true] => Surface::KLEIN(Twist::HORIZONTAL),
+                [false, true] => Surface::KLEIN(Twist::VERTICAL),
+                _ => return Err("a Klein bottle needs exactly one '*' size".to_string()),
+            },
+            Some('C') => Surface::CROSSSURFACE,
+            Some('S') => {
+                if width != height {
+                    return Err("a sphere must be square".to_string());
+                }
+                Surface::SPHERE
+            }
+            _ => return Err(format!("unknown topology '{}'", s)),
+        };
+        if surface != Surface::KLEIN(Twist::HORIZONTAL)
+            && surface != Surface::KLEIN(Twist::VERTICAL)
+            && starred.contains(&true)
+        {
+            return Err("only Klein bottles take a '*'".to_string());
+        }
+        if (x_shift != 0 || y_shift != 0) && surface != Surface::TORUS {
+            return Err("only a torus takes a shift".to_string());
+        }
+        if x_shift != 0 && y_shift != 0 {
+            return Err("a torus takes a shift on one pair of edges only".to_string());
+        }
+
+        Ok(Topology {
+            surface,
+            width,
+            height,
+            shift: (x_shift, y_shift),
+        })
+    }
+}
+
+// a size and the shift after it, if any
+fn split_shift(size: &str) -> Result<(&str, isize), String> {
+    match size.find(['+', '-']) {
+        Some(i) => match size[i..].trim_start_matches('+').parse::<isize>() {
+            Ok(shift) => Ok((&size[..i], shift)),
+            Err(_) => Err(format!("'{}' is not a valid shift", &size[i..])),
+        },
+        None => Ok((size, 0)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a position and the cell it maps to
+    type Mapping = ((isize, isize), Option<(usize, usize)>);
+
+    // checks where positions beyond the edges end up
+    fn assert_maps(topology: &str, mappings: &[Mapping]) {
+        let topology: Topology = topology.parse().unwrap();
+        for &((x, y), expected) in mappings {
+            assert_eq!(topology.map(x, y), expected, "{} at {:?}", topology, (x, y));
+        }
+    }
+
+    #[test]
+    fn plane_edges_are_dead() {
+        assert_maps(
+            "P4,3",
+            &[
+                ((0, 0), Some((0, 0))),
+                ((3, 2), Some((3, 2))),
+                ((-1, 0), None),
+                ((4, 1), None),
+                ((1, -1), None),
+                ((1, 3), None),
+                ((-1, -1), None),
+                ((4, 3), None),
+            ],
+        );
+    }
+
+    #[test]
+    fn torus_wraps_both_ways() {
+        assert_maps(
+            "T4,3",
+            &[
+                ((-1, 0), Some((3, 0))),
+                ((4, 2), Some((0, 2))),
+                ((0, -1), Some((0, 2))),
+                ((2, 3), Some((2, 0))),
+                ((-1, -1), Some((3, 2))),
+                ((4, 3), Some((0, 0))),
+                ((-5, 7), Some((3, 1))),
+            ],
+        );
+    }
+
+    #[test]
+    fn shifted_torus_moves_along_the_joined_edges() {
+        assert_maps(
+            "T4+1,3",
+            &[
+                ((0, 3), Some((1, 0))),
+                ((3, 3), Some((0, 0))),
+                ((0, -1), Some((3, 2))),
+                ((4, 0), Some((0, 0))),
+                ((-1, 1), Some((3, 1))),
+                ((4, 3), Some((1, 0))),
+            ],
+        );
+        assert_maps(
+            "T4,3-1",
+            &[
+                ((4, 1), Some((0, 0))),
+                ((4, 0), Some((0, 2))),
+                ((-1, 2), Some((3, 0))),
+                ((0, 3), Some((0, 0))),
+            ],
+        );
+    }
+
+    #[test]
+    fn klein_bottle_mirrors_across_the_twisted_edges() {
+        assert_maps(
+            "K4*,3",
+            &[
+                ((0, -1), Some((3, 2))),
+                ((1, 3), Some((2, 0))),
+                ((4, 0), Some((0, 0))),
+                ((-1, 1), Some((3, 1))),
+                ((-1, -1), Some((0, 2))),
+                ((4, 3), Some((3, 0))),
+            ],
+        );
+        assert_maps(
+            "K4,3*",
+            &[
+                ((-1, 0), Some((3, 2))),
+                ((4, 2), Some((0, 0))),
+                ((4, 1), Some((0, 1))),
+                ((0, 3), Some((0, 0))),
+                ((-1, -1), Some((3, 0))),
+            ],
+        );
+    }
+
+    #[test]
+    fn cross_surface_mirrors_every_edge_and_kills_corners() {
+        assert_maps(
+            "C4,3",
+            &[
+                ((0, -1), Some((3, 2))),
+                ((1, 3), Some((2, 0))),
+                ((-1, 0), Some((3, 2))),
+                ((4, 2), Some((0, 0))),
+                ((-1, -1), None),
+                ((4, -1), None),
+                ((-1, 3), None),
+                ((4, 3), None),
+            ],
+        );
+    }
+
+    #[test]
+    fn sphere_joins_neighboring_edges_and_kills_corners() {
+        assert_maps(
+            "S4",
+            &[
+                // the top edge meets the left edge, the bottom the right
+                ((2, -1), Some((0, 2))),
+                ((-1, 2), Some((2, 0))),
+                ((1, 4), Some((3, 1))),
+                ((4, 1), Some((1, 3))),
+                ((0, -1), Some((0, 0))),
+                ((-1, -1), None),
+                ((4, -1), None),
+                ((-1, 4), None),
+                ((4, 4), None),
+            ],
+        );
+    }
+
+    #[test]
+    fn parses_and_displays_suffixes() {
+        for suffix in &[
+            "P80,40", "T80,40", "T80+2,40", "T80,40-3", "K80*,40", "K80,40*", "C80,40", "S80",
+        ] {
+            assert_eq!(suffix.parse::<Topology>().unwrap().to_string(), *suffix);
+        }
+        assert_eq!("T5".parse::<Topology>().unwrap(), Topology::torus(5, 5));
+        for suffix in &[
+            "X80,40",
+            "T0,40",
+            "T80,40,2",
+            "K80,40",
+            "S80,40",
+            "P80*,40",
+            "P80+2,40",
+            "T80+1,40+1",
+            "T80+x,40",
+        ] {
+            assert!(suffix.parse::<Topology>().is_err(), "{}", suffix);
+        }
+    }
+}
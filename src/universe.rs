@@ -0,0 +1,326 @@
+use crate::bitgrid::BitGrid;
+use crate::engine::Engine;
+use crate::grid::Grid;
+use crate::hashlife::HashLife;
+use crate::neighborhood::Neighborhood;
+use crate::rule::{CellState, Change, Rule};
+use crate::sparse::Sparse;
+use crate::topology::{Surface, Topology};
+use std::collections::HashMap;
+
+/// How a Life-like universe is stored and stepped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    /// A grid of the size and topology given by the rule, or of a given size
+    /// and wrapping around at the edges.
+    NAIVE,
+    /// The same grid with a bit per cell, stepping 64 cells at once. The
+    /// default for rules it supports.
+    BITGRID,
+    /// An unbounded plane that only stores the cells that aren't dead.
+    SPARSE,
+    /// An unbounded plane stored as a memoized quadtree, stepping a power of
+    /// two generations at a time.
+    HASHLIFE,
+}
+
+impl Algorithm {
+    /// The fastest grid that runs `rule`.
+    pub fn default_for(rule: &Rule) -> Algorithm {
+        if BitGrid::supports(rule) {
+            Algorithm::BITGRID
+        } else {
+            Algorithm::NAIVE
+        }
+    }
+
+    /// The fastest unbounded plane that runs `rule`, if any does.
+    pub fn default_unbounded(rule: &Rule) -> Algorithm {
+        if HashLife::supports(rule) {
+            Algorithm::HASHLIFE
+        } else {
+            Algorithm::SPARSE
+        }
+    }
+}
+
+impl std::str::FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "naive" => Ok(Algorithm::NAIVE),
+            "bitgrid" => Ok(Algorithm::BITGRID),
+            "sparse" => Ok(Algorithm::SPARSE),
+            "hashlife" => Ok(Algorithm::HASHLIFE),
+            _ => Err(format!(
+                "unknown algorithm '{}' (expected naive, bitgrid, sparse or hashlife)",
+                s
+            )),
+        }
+    }
+}
+
+/// A Life-like universe: an engine running a rule, and the number of
+/// generations it has run for. Can also keep the age of every cell, whatever
+/// the engine.
+pub struct Universe {
+    engine: Box<dyn Engine>,
+    generation: u64,
+    // the generation each cell that isn't dead was born in, if ages are kept
+    births: Option<HashMap<(i64, i64), u64>>,
+    // the cells that changed in the last tick, copied out of the engine while
+    // ages are kept
+    changes: Vec<Change>,
+}
+
+impl Universe {
+    /// Runs `rule` with `algorithm`. The grids take the rule's topology, or
+    /// are a torus `size` cells wide and high if it has none, which fails if
+    /// that leaves no cells.
+    pub fn new(rule: Rule, algorithm: Algorithm, size: (usize, usize)) -> Result<Self, String> {
+        let engine: Box<dyn Engine> = match algorithm {
+            Algorithm::NAIVE | Algorithm::BITGRID => {
+                let topology = rule.topology().unwrap_or_else(|| {
+                    let (width, height) = size;
+                    let height = if rule.neighborhood() == Neighborhood::HEXAGONAL {
+                        // even, so the row shift lines up when wrapping
+                        height & !1
+                    } else {
+                        height
+                    };
+                    Topology::torus(width, height)
+                });
+                if topology.width == 0 || topology.height == 0 {
+                    return Err(format!(
+                        "a {}x{} grid has no cells",
+                        topology.width, topology.height
+                    ));
+                }
+                if rule.neighborhood() == Neighborhood::HEXAGONAL && !lines_up_hexagons(topology) {
+                    return Err(format!(
+                        "{} needs a plane, or a torus with an even height and shift",
+                        rule
+                    ));
+                }
+                if algorithm == Algorithm::BITGRID {
+                    Box::new(BitGrid::new(rule, topology)?)
+                } else {
+                    Box::new(Grid::new(rule, topology))
+                }
+            }
+            Algorithm::SPARSE | Algorithm::HASHLIFE if rule.topology().is_some() => {
+                return Err(format!("{} needs the naive or bitgrid algorithm", rule));
+            }
+            Algorithm::SPARSE => Box::new(Sparse::new(rule)?),
+            Algorithm::HASHLIFE => Box::new(HashLife::new(rule)?),
+        };
+        Ok(Universe::with_engine(engine))
+    }
+
+    /// Runs any engine, including ones from outside this crate.
+    pub fn with_engine(engine: Box<dyn Engine>) -> Self {
+        Universe {
+            engine,
+            generation: 0,
+            births: None,
+            changes: Vec::new(),
+        }
+    }
+
+    pub fn engine(&self) -> &dyn Engine {
+        &*self.engine
+    }
+
+    pub fn rule(&self) -> &Rule {
+        self.engine.rule()
+    }
+
+    pub fn topology(&self) -> Option<Topology> {
+        self.engine.topology()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Counts generations from `generation` on, such as after going back to
+    /// an earlier state. Cells born after it count as born in it.
+    pub fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+        if let Some(births) = &mut self.births {
+            for birth in births.values_mut() {
+                *birth = (*birth).min(generation);
+            }
+        }
+    }
+
+    /// Starts keeping how many generations ago each cell that isn't dead was
+    /// born, counting the ones that aren't dead now as just born. Costs a
+    /// little for every cell that changes, and a look at every cell after
+    /// steps that don't say which cells changed.
+    pub fn keep_ages(&mut self) {
+        if self.births.is_none() {
+            let mut births = HashMap::new();
+            count_births(&*self.engine, &mut births, self.generation);
+            self.births = Some(births);
+        }
+    }
+
+    /// How many generations ago a cell that isn't dead was born, if ages are
+    /// kept. Cells born during a step of more than one generation count as
+    /// born at its end.
+    pub fn age(&self, x: i64, y: i64) -> Option<u64> {
+        let birth = self.births.as_ref()?.get(&(x, y))?;
+        Some(self.generation - birth)
+    }
+
+    /// Whether a position is a cell, which is always true on an unbounded
+    /// plane.
+    pub fn contains(&self, x: i64, y: i64) -> bool {
+        match self.topology() {
+            Some(topology) => {
+                0 <= x && x < topology.width as i64 && 0 <= y && y < topology.height as i64
+            }
+            None => true,
+        }
+    }
+
+    pub fn get(&self, x: i64, y: i64) -> CellState {
+        self.engine.get(x, y)
+    }
+
+    pub fn set(&mut self, x: i64, y: i64, state: CellState) {
+        let contains = self.contains(x, y);
+        if let Some(births) = self.births.as_mut().filter(|_| contains) {
+            note_birth(births, self.generation, x, y, state);
+        }
+        self.engine.set(x, y, state);
+    }
+
+    /// Kills every cell.
+    pub fn clear(&mut self) {
+        if let Some((left, top, width, height)) = self.bounding_box() {
+            let mut cells = Vec::new();
+            self.cells_in(left, top, width, height, |x, y, _| cells.push((x, y)));
+            for (x, y) in cells {
+                self.set(x, y, CellState::DEAD);
+            }
+        }
+    }
+
+    /// Calls `f` with every cell in the given rectangle that isn't dead.
+    pub fn cells_in(
+        &self,
+        left: i64,
+        top: i64,
+        width: i64,
+        height: i64,
+        mut f: impl FnMut(i64, i64, CellState),
+    ) {
+        self.engine.cells_in(left, top, width, height, &mut f);
+    }
+
+    /// Advances a step and returns the cells that changed, or `None` if the
+    /// engine doesn't keep track of them.
+    pub fn tick(&mut self) -> Option<&[Change]> {
+        self.generation += self.engine.generations_per_tick();
+        let births = match &mut self.births {
+            Some(births) => births,
+            None => return self.engine.tick(),
+        };
+        self.changes.clear();
+        match self.engine.tick() {
+            Some(changes) => {
+                for change in changes {
+                    note_birth(births, self.generation, change.x, change.y, change.state);
+                }
+                self.changes.extend_from_slice(changes);
+            }
+            None => {
+                count_births(&*self.engine, births, self.generation);
+                return None;
+            }
+        }
+        Some(&self.changes)
+    }
+
+    /// Advances `generations` generations, in big steps on engines that can
+    /// take them.
+    pub fn step(&mut self, generations: u64) {
+        self.generation += generations;
+        self.engine.step(generations);
+        if let Some(births) = &mut self.births {
+            count_births(&*self.engine, births, self.generation);
+        }
+    }
+
+    /// Whether `tick` returns the cells that changed.
+    pub fn tracks_changes(&self) -> bool {
+        self.engine.tracks_changes()
+    }
+
+    /// The number of cells that aren't dead.
+    pub fn population(&self) -> u64 {
+        self.engine.population()
+    }
+
+    /// The smallest rectangle holding every cell that isn't dead, as `(left,
+    /// top, width, height)`, or `None` if they are all dead.
+    pub fn bounding_box(&self) -> Option<(i64, i64, i64, i64)> {
+        self.engine.bounding_box()
+    }
+
+    /// Each `tick` advances 2^`step_exponent()` generations.
+    pub fn step_exponent(&self) -> u8 {
+        self.engine.generations_per_tick().trailing_zeros() as u8
+    }
+
+    /// Only changes the step of engines that can advance more than one
+    /// generation at a time.
+    pub fn set_step_exponent(&mut self, exponent: u8) {
+        self.engine.set_step_exponent(exponent);
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.engine.set_threads(threads);
+    }
+}
+
+// whether wrapping keeps every row next to rows shifted the other way, as
+// the hexagonal neighborhood expects; twisted edges mirror the shift
+fn lines_up_hexagons(topology: Topology) -> bool {
+    match topology.surface {
+        Surface::PLANE => true,
+        Surface::TORUS => topology.height.is_multiple_of(2) && topology.shift.1 % 2 == 0,
+        _ => false,
+    }
+}
+
+// remembers when a cell was born, or forgets it once it is dead
+fn note_birth(
+    births: &mut HashMap<(i64, i64), u64>,
+    generation: u64,
+    x: i64,
+    y: i64,
+    state: CellState,
+) {
+    if state == CellState::DEAD {
+        births.remove(&(x, y));
+    } else {
+        births.entry((x, y)).or_insert(generation);
+    }
+}
+
+// brings the births up to date with the cells that aren't dead, when the
+// engine didn't say which ones changed
+fn count_births(engine: &dyn Engine, births: &mut HashMap<(i64, i64), u64>, generation: u64) {
+    let mut alive = HashMap::with_capacity(births.len());
+    if let Some((left, top, width, height)) = engine.bounding_box() {
+        engine.cells_in(left, top, width, height, &mut |x, y, _| {
+            let birth = births.get(&(x, y)).copied().unwrap_or(generation);
+            alive.insert((x, y), birth);
+        });
+    }
+    *births = alive;
+}
@@ -0,0 +1,435 @@
+//! Runs every engine on the same patterns and checks that they agree
+//! generation by generation, and with what is known about the patterns.
+
+use life::{Algorithm, CellState, Rule, Universe};
+
+const ALGORITHMS: [Algorithm; 4] = [
+    Algorithm::NAIVE,
+    Algorithm::BITGRID,
+    Algorithm::SPARSE,
+    Algorithm::HASHLIFE,
+];
+
+// the grids are planes this big, so patterns near their middle behave as on
+// the unbounded plane until they come within reach of the edges
+const SIZE: i64 = 160;
+
+const GLIDER: &str = "
+.o.
+..o
+ooo";
+
+const LWSS: &str = "
+.o..o
+o....
+o...o
+oooo.";
+
+const R_PENTOMINO: &str = "
+.oo
+oo.
+.o.";
+
+const DIEHARD: &str = "
+......o.
+oo......
+.o...ooo";
+
+const ACORN: &str = "
+.o.....
+...o...
+oo..ooo";
+
+const GOSPER_GUN: &str = "
+........................o...........
+......................o.o...........
+............oo......oo............oo
+...........o...o....oo............oo
+oo........o.....o...oo..............
+oo........o...o.oo....o.o...........
+..........o.....o.......o...........
+...........o...o....................
+............oo......................";
+
+// every engine that runs the rule, the grids as planes `SIZE` cells square
+fn universes(rulestring: &str) -> Vec<(Algorithm, Universe)> {
+    ALGORITHMS
+        .iter()
+        .filter_map(|&algorithm| {
+            let rulestring = match algorithm {
+                Algorithm::NAIVE | Algorithm::BITGRID => {
+                    format!("{}:P{},{}", rulestring, SIZE, SIZE)
+                }
+                _ => rulestring.to_string(),
+            };
+            let rule: Rule = rulestring.parse().unwrap();
+            let universe = Universe::new(rule, algorithm, (0, 0)).ok()?;
+            Some((algorithm, universe))
+        })
+        .collect()
+}
+
+fn place(universe: &mut Universe, pattern: &str, left: i64, top: i64) {
+    for (y, line) in pattern.trim().lines().enumerate() {
+        for (x, c) in line.chars().enumerate() {
+            if c == 'o' {
+                universe.set(left + x as i64, top + y as i64, CellState::ALIVE);
+            }
+        }
+    }
+}
+
+// everything the engines should agree on: the generation, population,
+// bounding box and the cells that aren't dead, in a fixed order
+type Snapshot = (u64, u64, Option<(i64, i64, i64, i64)>, Vec<(i64, i64, u8)>);
+
+fn snapshot(universe: &Universe) -> Snapshot {
+    let bounding_box = universe.bounding_box();
+    let mut cells = Vec::new();
+    if let Some((left, top, width, height)) = bounding_box {
+        universe.cells_in(left, top, width, height, |x, y, state| {
+            cells.push((x, y, state.0))
+        });
+    }
+    cells.sort_unstable();
+    (
+        universe.generation(),
+        universe.population(),
+        bounding_box,
+        cells,
+    )
+}
+
+// checks that every universe is in the same state as the first one
+fn assert_agree(universes: &[(Algorithm, Universe)], what: &str) {
+    let (first, expected) = &universes[0];
+    let expected = snapshot(expected);
+    for (algorithm, universe) in &universes[1..] {
+        assert!(
+            snapshot(universe) == expected,
+            "{}: {:?} and {:?} differ",
+            what,
+            first,
+            algorithm
+        );
+    }
+}
+
+fn run_pattern(rulestring: &str, pattern: &str, generations: u64) {
+    let mut universes = universes(rulestring);
+    assert!(
+        universes.len() > 1,
+        "{} runs on fewer than two engines",
+        rulestring
+    );
+    for (_, universe) in &mut universes {
+        place(universe, pattern, SIZE / 2, SIZE / 2);
+    }
+    for generation in 0..=generations {
+        assert_agree(
+            &universes,
+            &format!("{} generation {}", rulestring, generation),
+        );
+        for (_, universe) in &mut universes {
+            universe.tick();
+        }
+    }
+}
+
+// xorshift, so soups are the same on every run
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+// fills a square of `size` cells at the middle of every universe with random
+// states, each not dead with probability `density`
+fn soup(universes: &mut [(Algorithm, Universe)], random: &mut Random, size: i64, density: f64) {
+    let states = universes[0].1.rule().states() as u64;
+    let corner = (SIZE - size) / 2;
+    for y in corner..corner + size {
+        for x in corner..corner + size {
+            if (random.next() % 1000) as f64 >= density * 1000.0 {
+                continue;
+            }
+            let state = CellState((1 + random.next() % (states - 1)) as u8);
+            for (_, universe) in universes.iter_mut() {
+                universe.set(x, y, state);
+            }
+        }
+    }
+}
+
+#[test]
+fn patterns_agree() {
+    for pattern in &[GLIDER, LWSS, R_PENTOMINO, DIEHARD, ACORN, GOSPER_GUN] {
+        run_pattern("B3/S23", pattern, 100);
+    }
+}
+
+#[test]
+fn soups_agree() {
+    let rules = [
+        ("B3/S23", 60),
+        ("B36/S23", 60),
+        ("B3678/S34678", 60),
+        ("B2/S013V", 60),
+        ("B2/S34H", 60),
+        ("B2-a/S12", 60),
+        ("B2/S/C4", 60),
+        ("WireWorld", 60),
+        // range 2, so patterns spread twice as fast
+        ("R2,C0,M1,S2..5,B3..4,NM", 30),
+    ];
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    for &(rulestring, generations) in &rules {
+        for &density in &[0.2, 0.5] {
+            let mut universes = universes(rulestring);
+            assert!(
+                universes.len() > 1,
+                "{} runs on fewer than two engines",
+                rulestring
+            );
+            soup(&mut universes, &mut random, 32, density);
+            for generation in 0..=generations {
+                let what = format!("{} soup generation {}", rulestring, generation);
+                assert_agree(&universes, &what);
+                for (_, universe) in &mut universes {
+                    universe.tick();
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn grids_agree_on_every_topology() {
+    let topologies = [
+        "T60,40", "T60+7,40", "T60,40-5", "P60,40", "K60*,40", "K60,40*", "C60,40", "S50",
+    ];
+    let mut random = Random(0x9e37_79b9_7f4a_7c15);
+    for rulestring in &["B3/S23", "B36/S23", "B2/S013V", "B0/S8"] {
+        for topology in &topologies {
+            let rule: Rule = format!("{}:{}", rulestring, topology).parse().unwrap();
+            let mut naive = Universe::new(rule.clone(), Algorithm::NAIVE, (0, 0)).unwrap();
+            let mut bitgrid = Universe::new(rule.clone(), Algorithm::BITGRID, (0, 0)).unwrap();
+            let (width, height) = {
+                let topology = rule.topology().unwrap();
+                (topology.width as i64, topology.height as i64)
+            };
+            for y in 0..height {
+                for x in 0..width {
+                    if random.next().is_multiple_of(3) {
+                        naive.set(x, y, CellState::ALIVE);
+                        bitgrid.set(x, y, CellState::ALIVE);
+                    }
+                }
+            }
+
+            for generation in 0..100 {
+                let what = format!("{} generation {}", rule, generation);
+                let mut expected = naive.tick().unwrap().to_vec();
+                let mut changes = bitgrid.tick().unwrap().to_vec();
+                expected.sort_by_key(|change| (change.y, change.x));
+                changes.sort_by_key(|change| (change.y, change.x));
+                assert_eq!(changes, expected, "{}: changes differ", what);
+                assert_eq!(bitgrid.population(), naive.population(), "{}", what);
+                assert_eq!(bitgrid.bounding_box(), naive.bounding_box(), "{}", what);
+            }
+        }
+    }
+}
+
+#[test]
+fn stepping_many_generations_matches_ticking() {
+    let rule: Rule = "B3/S23".parse().unwrap();
+    for &generations in &[1, 7, 64, 100, 1000] {
+        let mut ticked = Universe::new(rule.clone(), Algorithm::SPARSE, (0, 0)).unwrap();
+        let mut stepped = Universe::new(rule.clone(), Algorithm::HASHLIFE, (0, 0)).unwrap();
+        place(&mut ticked, GOSPER_GUN, 0, 0);
+        place(&mut stepped, GOSPER_GUN, 0, 0);
+        for _ in 0..generations {
+            ticked.tick();
+        }
+        stepped.step(generations);
+
+        assert_eq!(stepped.generation(), generations);
+        assert_eq!(stepped.population(), ticked.population());
+        let (left, top, width, height) = ticked.bounding_box().unwrap();
+        assert_eq!(stepped.bounding_box(), Some((left, top, width, height)));
+        let mut expected = Vec::new();
+        ticked.cells_in(left, top, width, height, |x, y, _| expected.push((x, y)));
+        let mut actual = Vec::new();
+        stepped.cells_in(left, top, width, height, |x, y, _| actual.push((x, y)));
+        expected.sort_unstable();
+        actual.sort_unstable();
+        assert_eq!(actual, expected, "after {} generations", generations);
+    }
+}
+
+#[test]
+fn glider_moves_one_cell_diagonally_every_four_generations() {
+    for (algorithm, mut universe) in universes("B3/S23") {
+        place(&mut universe, GLIDER, SIZE / 2, SIZE / 2);
+        let start = universe.bounding_box();
+        universe.step(4);
+        let (left, top, width, height) = start.unwrap();
+        assert_eq!(
+            universe.bounding_box(),
+            Some((left + 1, top + 1, width, height)),
+            "{:?}",
+            algorithm
+        );
+        assert_eq!(universe.population(), 5, "{:?}", algorithm);
+    }
+}
+
+#[test]
+fn diehard_dies_after_130_generations() {
+    for (algorithm, mut universe) in universes("B3/S23") {
+        place(&mut universe, DIEHARD, SIZE / 2, SIZE / 2);
+        universe.step(129);
+        assert!(universe.population() > 0, "{:?}", algorithm);
+        universe.step(1);
+        assert_eq!(universe.population(), 0, "{:?}", algorithm);
+        assert_eq!(universe.bounding_box(), None, "{:?}", algorithm);
+    }
+}
+
+#[test]
+fn r_pentomino_settles_at_116_cells() {
+    // its gliders get far enough to hit the edges of the grids
+    for &algorithm in &[Algorithm::SPARSE, Algorithm::HASHLIFE] {
+        let mut universe = Universe::new("B3/S23".parse().unwrap(), algorithm, (0, 0)).unwrap();
+        place(&mut universe, R_PENTOMINO, 0, 0);
+        universe.step(1103);
+        assert_eq!(universe.population(), 116, "{:?}", algorithm);
+        let before = universe.population();
+        universe.step(2);
+        assert_eq!(universe.population(), before, "{:?}", algorithm);
+    }
+}
+
+#[test]
+fn grids_without_cells_are_rejected() {
+    for rulestring in &["B3/S23", "B2/S34H"] {
+        let rule: Rule = rulestring.parse().unwrap();
+        for &algorithm in &[Algorithm::NAIVE, Algorithm::BITGRID] {
+            for &size in &[(0, 0), (0, 5), (5, 0)] {
+                assert!(Universe::new(rule.clone(), algorithm, size).is_err());
+            }
+        }
+    }
+    // hexagonal grids have an even number of rows
+    let rule: Rule = "B2/S34H".parse().unwrap();
+    assert!(Universe::new(rule.clone(), Algorithm::NAIVE, (5, 1)).is_err());
+    let mut universe = Universe::new(rule, Algorithm::NAIVE, (1, 2)).unwrap();
+    universe.set(0, 0, CellState::ALIVE);
+    universe.tick();
+}
+
+#[test]
+fn threads_agree_with_one_thread() {
+    let rules = [
+        ("B3/S23", &[Algorithm::NAIVE, Algorithm::BITGRID][..]),
+        ("B2/S013V", &[Algorithm::NAIVE, Algorithm::BITGRID][..]),
+        ("B2/S/C3", &[Algorithm::NAIVE][..]),
+        ("R2,C0,M1,S2..5,B3..4,NM", &[Algorithm::NAIVE][..]),
+    ];
+    // odd sizes, so the bands of rows come out uneven, and big enough to be
+    // split at all: a band takes at least 1024 cells, or 1024 words of 64
+    // cells on the bitgrid
+    let topologies = |algorithm| match algorithm {
+        Algorithm::BITGRID => ["T453,263", "P453,263", "K453*,263", "C453,263", "S347"],
+        _ => ["T121,67", "P121,67", "K121*,67", "C121,67", "S91"],
+    };
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    for (rulestring, algorithms) in &rules {
+        for (algorithm, topology) in algorithms
+            .iter()
+            .flat_map(|&algorithm| topologies(algorithm).map(|topology| (algorithm, topology)))
+        {
+            let rule: Rule = format!("{}:{}", rulestring, topology).parse().unwrap();
+            let (width, height) = {
+                let topology = rule.topology().unwrap();
+                (topology.width as i64, topology.height as i64)
+            };
+            let states = rule.states() as u64;
+            let mut cells = Vec::new();
+            for y in 0..height {
+                for x in 0..width {
+                    if random.next().is_multiple_of(3) {
+                        let state = CellState((1 + random.next() % (states - 1)) as u8);
+                        cells.push((x, y, state));
+                    }
+                }
+            }
+
+            let mut universes: Vec<Universe> = [1, 2, 7, 64]
+                .iter()
+                .map(|&threads| {
+                    let mut universe = Universe::new(rule.clone(), algorithm, (0, 0)).unwrap();
+                    universe.set_threads(threads);
+                    for &(x, y, state) in &cells {
+                        universe.set(x, y, state);
+                    }
+                    universe
+                })
+                .collect();
+            for generation in 0..20 {
+                let changes: Vec<_> = universes
+                    .iter_mut()
+                    .map(|universe| {
+                        let mut changes = universe.tick().unwrap().to_vec();
+                        changes.sort_by_key(|change| (change.y, change.x));
+                        changes
+                    })
+                    .collect();
+                for theirs in &changes[1..] {
+                    assert_eq!(
+                        theirs, &changes[0],
+                        "{} {:?} generation {}: changes differ",
+                        rule, algorithm, generation
+                    );
+                }
+            }
+            // the same changes leave the same cells, which are only compared
+            // once as the grids are large
+            let expected = snapshot(&universes[0]);
+            for universe in &universes[1..] {
+                assert!(
+                    snapshot(universe) == expected,
+                    "{} {:?}: cells differ",
+                    rule,
+                    algorithm
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn hexagonal_grids_keep_rows_lined_up() {
+    for (topology, lines_up) in &[
+        ("P60,41", true),
+        ("T60,40", true),
+        ("T60+3,40", true),
+        ("T60,40+2", true),
+        ("T60,41", false),
+        ("T60,40+1", false),
+        ("K60*,40", false),
+        ("K60,40*", false),
+        ("C60,40", false),
+        ("S40", false),
+    ] {
+        let rule: Rule = format!("B2/S34H:{}", topology).parse().unwrap();
+        let universe = Universe::new(rule, Algorithm::NAIVE, (0, 0));
+        assert_eq!(universe.is_ok(), *lines_up, "{}", topology);
+    }
+}
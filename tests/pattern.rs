@@ -0,0 +1,126 @@
+//! Reads patterns in RLE and plaintext, and checks what they read as.
+
+use life::{Algorithm, CellState, Pattern, Rule, Universe};
+
+fn parse(s: &str) -> Result<Pattern, String> {
+    s.parse::<Pattern>()
+}
+
+#[test]
+fn rejects_bad_rle() {
+    // states above 255
+    assert!(parse("x = 1, y = 1\nyX!").is_err());
+    assert!(parse("x = 1, y = 1\nyP!").is_err());
+    // a prefix without a state after it
+    assert!(parse("x = 1, y = 1\npo!").is_err());
+    // counts too big for an i64, or for memory
+    assert!(parse("x = 1, y = 1\n99999999999999999999o!").is_err());
+    assert!(parse("x = 1, y = 1\n1000000000o!").is_err());
+    assert!(parse("x = 1, y = 1\n1000000000$o!").is_err());
+    assert!(parse("x = 1, y = 1\nbz!").is_err());
+}
+
+#[test]
+fn reads_the_largest_state_rle_can_write() {
+    // one more than the most states a rule can have, so without a rule
+    let pattern = parse("x = 1, y = 1\nyO!").unwrap();
+    assert_eq!(pattern.cells, vec![(0, 0, CellState(255))]);
+}
+
+#[test]
+fn reads_a_rule_with_commas() {
+    let pattern = parse("#N Glider\nx = 3, y = 3, rule = B3/S23:T80,40\nbo$2bo$3o!").unwrap();
+    assert_eq!((pattern.width, pattern.height), (3, 3));
+    assert_eq!(pattern.rule.as_deref(), Some("B3/S23:T80,40"));
+    assert_eq!(pattern.cells.len(), 5);
+}
+
+#[test]
+fn reads_runs_of_rows() {
+    let pattern = parse("x = 2, y = 4\no3$bo\n!").unwrap();
+    assert_eq!(
+        pattern.cells,
+        vec![(0, 0, CellState(1)), (1, 3, CellState(1))]
+    );
+    assert_eq!((pattern.width, pattern.height), (2, 4));
+}
+
+#[test]
+fn reads_multistate_letters() {
+    let pattern = parse("x = 6, y = 2, rule = /2/41\n.A2B$XpApP!").unwrap();
+    let states: Vec<_> = pattern
+        .cells
+        .iter()
+        .map(|&(x, y, state)| (x, y, state.0))
+        .collect();
+    assert_eq!(
+        states,
+        vec![
+            (1, 0, 1),
+            (2, 0, 2),
+            (3, 0, 2),
+            (0, 1, 24),
+            (1, 1, 25),
+            (2, 1, 40)
+        ]
+    );
+    let rule: Rule = pattern.rule.as_deref().unwrap().parse().unwrap();
+    let mut universe = Universe::new(rule, Algorithm::SPARSE, (0, 0)).unwrap();
+    pattern.place(&mut universe, 0, 0).unwrap();
+}
+
+#[test]
+fn reads_plaintext() {
+    let pattern = parse("!Name: Glider\n!\n.O.\n..O\nOOO\n").unwrap();
+    assert_eq!((pattern.width, pattern.height), (3, 3));
+    assert_eq!(pattern.rule, None);
+    assert_eq!(pattern.cells[0], (1, 0, CellState(1)));
+    assert_eq!(pattern.cells.len(), 5);
+    assert!(parse(".O.\n.X.\n").is_err());
+}
+
+#[test]
+fn reads_what_it_writes() {
+    // a row longer than a line, and one of every state, more than any rule
+    // has
+    let long = parse(&format!(
+        "x = 200, y = 1, rule = B3/S23\n{}!",
+        "bo".repeat(100)
+    ))
+    .unwrap();
+    let states: String = (1..=255u8)
+        .map(|state| format!("{}$", tag(state)))
+        .collect();
+    let multistate = parse(&format!("x = 1, y = 255\n{}!", states)).unwrap();
+    for pattern in [long, multistate] {
+        let written = pattern.to_string();
+        assert!(written.lines().all(|line| line.len() <= 70));
+        assert_eq!(parse(&written).unwrap(), pattern);
+    }
+}
+
+#[test]
+fn places_only_states_the_rule_has() {
+    let pattern = parse("x = 3, y = 1, rule = /2/4\nABC!").unwrap();
+    let rule: Rule = "B2/S/C3".parse().unwrap();
+    let mut universe = Universe::new(rule, Algorithm::SPARSE, (0, 0)).unwrap();
+    assert!(pattern.place(&mut universe, 0, 0).is_err());
+    assert_eq!(universe.bounding_box(), None);
+
+    let rule: Rule = "B2/S/C4".parse().unwrap();
+    let mut universe = Universe::new(rule, Algorithm::SPARSE, (0, 0)).unwrap();
+    pattern.place(&mut universe, 5, 5).unwrap();
+    assert_eq!(universe.get(7, 5), CellState(3));
+}
+
+// the RLE letters for a state
+fn tag(state: u8) -> String {
+    match state {
+        1..=24 => ((b'A' + state - 1) as char).to_string(),
+        _ => format!(
+            "{}{}",
+            (b'p' + (state - 25) / 24) as char,
+            (b'A' + (state - 25) % 24) as char
+        ),
+    }
+}